@@ -0,0 +1,111 @@
+fn main() {
+	println!("cargo:rerun-if-changed=build.rs");
+
+	#[cfg(feature = "latest-list")]
+	latest_list::generate();
+}
+
+/// Fetches the latest list of agents from the ai.robots.txt project for `AI_AGENTS`, falling back
+/// to the list bundled with the crate if it can't be fetched
+#[cfg(feature = "latest-list")]
+mod latest_list {
+	use std::{env, fs, path::PathBuf, time::Duration};
+
+	const ROBOTS_JSON_URL: &str =
+		"https://raw.githubusercontent.com/ai-robots-txt/ai.robots.txt/main/robots.json";
+	// set to the path of a robots.json to use that instead of downloading one
+	const ROBOTS_JSON_ENV: &str = "TOWER_NO_AI_ROBOTS_JSON";
+	// the list is only downloaded when this script runs, which cargo only does again when this
+	// (or something else it's watching) changes, so changing this is a way to ask for a new one
+	const REFRESH_ENV: &str = "TOWER_NO_AI_REFRESH";
+
+	pub(super) fn generate() {
+		println!("cargo:rerun-if-env-changed={ROBOTS_JSON_ENV}");
+		println!("cargo:rerun-if-env-changed={REFRESH_ENV}");
+
+		let (list, categories) = match fetch() {
+			Ok(agents) => {
+				let mut list = String::from("&[\n");
+				let mut categories = String::from("&[\n");
+				for (agent, category) in agents {
+					list.push_str(&format!("\t{agent:?},\n"));
+					if let Some(category) = category {
+						categories
+							.push_str(&format!("\t({agent:?}, AgentCategory::{category}),\n"));
+					}
+				}
+				list.push(']');
+				categories.push(']');
+				(list, categories)
+			}
+			Err(e) => {
+				println!(
+					"cargo:warning=couldn't get the latest agent list ({e}), so the bundled one \
+					 will be used instead"
+				);
+				(String::from("BUNDLED_AGENTS"), String::from("&[]"))
+			}
+		};
+
+		let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+		fs::write(out.join("ai_agents.rs"), list).unwrap();
+		fs::write(out.join("agent_categories.rs"), categories).unwrap();
+	}
+
+	/// Returns every agent in the list, along with the `AgentCategory` that its description
+	/// suggests it falls into, if it suggests one
+	fn fetch() -> Result<Vec<(String, Option<&'static str>)>, String> {
+		let json = match env::var_os(ROBOTS_JSON_ENV) {
+			Some(path) => {
+				println!("cargo:rerun-if-changed={}", PathBuf::from(&path).display());
+				fs::read_to_string(path).map_err(|e| e.to_string())?
+			}
+			None => ureq::AgentBuilder::new()
+				.timeout(Duration::from_secs(10))
+				.build()
+				.get(ROBOTS_JSON_URL)
+				.call()
+				.map_err(|e| e.to_string())?
+				.into_string()
+				.map_err(|e| e.to_string())?
+		};
+
+		let robots: serde_json::Map<String, serde_json::Value> =
+			serde_json::from_str(&json).map_err(|e| e.to_string())?;
+		if robots.is_empty() {
+			return Err(String::from("the list was empty"));
+		}
+
+		Ok(robots
+			.into_iter()
+			.map(|(agent, info)| {
+				let category = category(&info);
+				(agent, category)
+			})
+			.collect())
+	}
+
+	/// Guesses the category of an agent from what the list says about what it does. Agents that
+	/// this can't tell are left as scrapers.
+	fn category(info: &serde_json::Value) -> Option<&'static str> {
+		let described = ["function", "description"]
+			.into_iter()
+			.filter_map(|field| info.get(field)?.as_str())
+			.collect::<Vec<_>>()
+			.join(" ")
+			.to_lowercase();
+
+		if described.contains("train") {
+			Some("Training")
+		} else if described.contains("search") {
+			Some("Search")
+		} else if ["assistant", "on behalf", "user request", "user prompt"]
+			.into_iter()
+			.any(|phrase| described.contains(phrase))
+		{
+			Some("Assistant")
+		} else {
+			None
+		}
+	}
+}
@@ -0,0 +1,195 @@
+use std::sync::OnceLock;
+
+use bytes::Bytes;
+use http::{header::CONTENT_TYPE, Response, StatusCode};
+
+use crate::tarpit::Tarpitted;
+
+/// What a [`NoAiService`] does with a request once it's decided that the request came from an AI
+/// agent
+///
+/// [`NoAiService`]: crate::NoAiService
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum Action {
+	/// Redirect the request to the URL that the [`NoAiLayer`] was created with, with a
+	/// `301 Moved Permanently` that caches are told not to store. This is the default.
+	///
+	/// [`NoAiLayer`]: crate::NoAiLayer
+	Redirect,
+	/// Redirect the request back to the URL it asked for, with a new cache-busting query parameter
+	/// (as set with [`NoAiLayer::refetch_param`]) each time, so that an agent which follows
+	/// redirects keeps asking for the same thing over and over without ever getting it. This costs
+	/// next to nothing to serve, and doesn't send traffic anywhere else.
+	///
+	/// This is a `307 Temporary Redirect` that caches are told not to store, so that people are
+	/// never sent a copy of it and stuck in the loop themselves.
+	///
+	/// [`NoAiLayer::refetch_param`]: crate::NoAiLayer::refetch_param
+	RedirectLoop,
+	/// Respond with the given status code and an empty body (or the message set with
+	/// [`NoAiLayer::block_message`])
+	///
+	/// [`NoAiLayer::block_message`]: crate::NoAiLayer::block_message
+	Status(
+		#[cfg_attr(
+			feature = "serde",
+			serde(deserialize_with = "crate::config::status_code")
+		)]
+		StatusCode
+	),
+	/// Respond with a `200 OK` and the given number of bytes of meaningless, randomly generated
+	/// text, so that the agent wastes its bandwidth and gets nothing of value out of it. This is
+	/// capped at 1 MiB, since the response has to be sent all at once. See [`Action::Tarpit`] for
+	/// a response that's sent slowly instead.
+	Garbage(usize),
+	/// Respond with a `200 OK` and mark the response so that a [`TarpitLayer`] around this layer
+	/// sends meaningless text in its place, a few bytes at a time, to keep the agent waiting for as
+	/// long as possible. Without a [`TarpitLayer`], the request just gets an empty body.
+	///
+	/// [`TarpitLayer`]: crate::TarpitLayer
+	Tarpit,
+	/// Pass the request on to the inner service as if it hadn't been detected, but mark it so that
+	/// a [`WatermarkLayer`] between this layer and the inner service inserts hidden snippets into
+	/// the page that it gets back. Without a [`WatermarkLayer`], the request just gets the real
+	/// page.
+	///
+	/// Requests which are detected by [`NoAiLayer::block_if_async`] have already been passed on
+	/// by the time that they're detected, so they can't be watermarked, and are let through as
+	/// they are.
+	///
+	/// [`WatermarkLayer`]: crate::WatermarkLayer
+	/// [`NoAiLayer::block_if_async`]: crate::NoAiLayer::block_if_async
+	Watermark,
+	/// Pass the request on to the alternate service given to [`NoAiLayer::alternate`] instead of
+	/// the inner service, e.g. one which serves a summary-only version of each page. Without an
+	/// alternate service, the request is redirected as with [`Action::Redirect`].
+	///
+	/// Like with [`Action::Watermark`], requests which are detected by
+	/// [`NoAiLayer::block_if_async`] have already been passed on to the inner service by the time
+	/// that they're detected, so they're let through as they are.
+	///
+	/// [`NoAiLayer::alternate`]: crate::NoAiLayer::alternate
+	/// [`NoAiLayer::block_if_async`]: crate::NoAiLayer::block_if_async
+	Alternate,
+	/// Respond with a `200 OK` and the image set with [`NoAiLayer::placeholder_image`], whatever
+	/// was asked for, and tell caches not to store it (so that it's never served to anyone else).
+	/// Without a placeholder image, the request is redirected as with [`Action::Redirect`].
+	///
+	/// [`NoAiLayer::placeholder_image`]: crate::NoAiLayer::placeholder_image
+	Image,
+	/// Respond with a page that only lets the client through once it's shown that it can run
+	/// JavaScript, as configured with [`NoAiLayer::challenge`]. See [`Challenge`] for more.
+	///
+	/// Requires the `challenge` feature.
+	///
+	/// [`NoAiLayer::challenge`]: crate::NoAiLayer::challenge
+	/// [`Challenge`]: crate::Challenge
+	#[cfg(feature = "challenge")]
+	Challenge
+}
+
+impl Action {
+	/// The name of this kind of action in snake_case, as it's written in configs and logs
+	pub(crate) fn name(&self) -> &'static str {
+		match self {
+			Self::Redirect => "redirect",
+			Self::RedirectLoop => "redirect_loop",
+			Self::Status(_) => "status",
+			Self::Garbage(_) => "garbage",
+			Self::Tarpit => "tarpit",
+			Self::Watermark => "watermark",
+			Self::Alternate => "alternate",
+			Self::Image => "image",
+			#[cfg(feature = "challenge")]
+			Self::Challenge => "challenge"
+		}
+	}
+
+	/// Returns whether requests given this action are still passed on to the inner service
+	pub(crate) fn passes_through(&self) -> bool {
+		matches!(self, Self::Watermark)
+	}
+
+	/// Builds the response for every action except [`Action::Redirect`], [`Action::RedirectLoop`],
+	/// [`Action::Image`], and (with the `challenge` feature) the challenge, which need to know
+	/// about the layer (and request) that they're responding for, and [`Action::Watermark`] and
+	/// [`Action::Alternate`], which don't respond themselves. `message` is the plain text body for
+	/// a [`Action::Status`], if there should be one.
+	pub(crate) fn respond<RespBody>(&self, message: Option<&Bytes>) -> Option<Response<RespBody>>
+	where
+		RespBody: From<Bytes>
+	{
+		match self {
+			Self::Redirect
+			| Self::RedirectLoop
+			| Self::Watermark
+			| Self::Alternate
+			| Self::Image => None,
+			#[cfg(feature = "challenge")]
+			Self::Challenge => None,
+			Self::Status(status) => {
+				let builder = Response::builder().status(*status);
+				Some(
+					match message {
+						Some(message) => builder
+							.header(CONTENT_TYPE, "text/plain; charset=utf-8")
+							.body(RespBody::from(message.clone())),
+						None => builder.body(RespBody::from(Bytes::new()))
+					}
+					.unwrap()
+				)
+			}
+			Self::Garbage(len) => Some(
+				Response::builder()
+					.status(StatusCode::OK)
+					.header(CONTENT_TYPE, "text/plain; charset=utf-8")
+					.body(RespBody::from(garbage(*len)))
+					.unwrap()
+			),
+			Self::Tarpit => Some(
+				Response::builder()
+					.status(StatusCode::OK)
+					.header(CONTENT_TYPE, "text/plain; charset=utf-8")
+					.extension(Tarpitted)
+					.body(RespBody::from(Bytes::new()))
+					.unwrap()
+			)
+		}
+	}
+}
+
+// responses can only be built from a single `Bytes`, so anything bigger than this would need a
+// new allocation for every request
+const GENERATED_LEN: usize = 1 << 20;
+
+/// Returns `len` bytes (but no more than [`GENERATED_LEN`]) of random-looking lowercase words.
+/// These are all generated once and then sliced up, so that serving garbage to a lot of bots
+/// doesn't cost us anything more than serving it to one.
+pub(crate) fn garbage(len: usize) -> Bytes {
+	static GARBAGE: OnceLock<Bytes> = OnceLock::new();
+
+	let generated = GARBAGE.get_or_init(|| {
+		// a simple xorshift is plenty here; this doesn't need to be unpredictable, just useless
+		let mut state = 0x2545_f491_4f6c_dd1d_u64;
+		let mut next = move || {
+			state ^= state << 13;
+			state ^= state >> 7;
+			state ^= state << 17;
+			state
+		};
+
+		let mut text = Vec::with_capacity(GENERATED_LEN);
+		while text.len() < GENERATED_LEN {
+			let word_len = 2 + next() % 9;
+			text.extend((0..word_len).map(|_| b'a' + (next() % 26) as u8));
+			text.push(if next() % 12 == 0 { b'\n' } else { b' ' });
+		}
+		text.truncate(GENERATED_LEN);
+		Bytes::from(text)
+	});
+
+	generated.slice(..len.min(GENERATED_LEN))
+}
@@ -0,0 +1,245 @@
+use http::{HeaderValue, Uri};
+
+/// The User-Agent patterns checked for and redirected if present. Each of these can be sorted into
+/// an [`AgentCategory`] with [`AgentCategory::of`].
+///
+/// With the `latest-list` feature, this is the latest list from the
+/// [ai.robots.txt](https://github.com/ai-robots-txt/ai.robots.txt) project at the time the crate
+/// was built (or the list bundled with the crate, if that couldn't be downloaded), so that new
+/// agents are blocked without waiting for a new release of this crate. Set the
+/// `TOWER_NO_AI_ROBOTS_JSON` environment variable to the path of a `robots.json` from that project
+/// when building to use that instead of downloading it. The agents that are new in that list are
+/// sorted into categories by what it says that they do.
+///
+/// Cargo only downloads the list again when it rebuilds this crate from scratch (e.g. after a
+/// `cargo clean`), or when the value of the `TOWER_NO_AI_REFRESH` environment variable changes,
+/// so set that to e.g. the current date to get the latest one.
+pub static AI_AGENTS: &[&str] = AGENT_LIST;
+
+#[cfg(not(feature = "latest-list"))]
+pub(crate) const AGENT_LIST: &[&str] = BUNDLED_AGENTS;
+
+#[cfg(feature = "latest-list")]
+pub(crate) const AGENT_LIST: &[&str] = include!(concat!(env!("OUT_DIR"), "/ai_agents.rs"));
+
+// the categories of the agents in the latest list, as guessed from its descriptions of them
+#[cfg(feature = "latest-list")]
+const LATEST_CATEGORIES: &[(&str, AgentCategory)] =
+	include!(concat!(env!("OUT_DIR"), "/agent_categories.rs"));
+
+// the list that this version of the crate was released with
+#[cfg_attr(feature = "latest-list", allow(dead_code))]
+const BUNDLED_AGENTS: &[&str] = &[
+	"AI2Bot",
+	"Ai2Bot-Dolma",
+	"AdsBot-Google2",
+	"Amazonbot",
+	"anthropic-ai",
+	"Applebot",
+	"Applebot-Extended",
+	"ArcMobile",
+	"AwarioRssBot",
+	"AwarioSmartBot",
+	"Bytespider",
+	"CCBot",
+	"ChatGPT-User",
+	"Claude-Web",
+	"ClaudeBot",
+	"cohere-ai",
+	"DataForSeoBot",
+	"Diffbot",
+	"DuckAssistBot",
+	"FacebookBot",
+	"FriendlyCrawler",
+	"Google-Extended",
+	"Googlebot-Image",
+	"GoogleOther",
+	"GoogleOther-Image",
+	"GoogleOther-Video",
+	"GPTBot",
+	"iaskspider/2.0",
+	"ICC-Crawler",
+	"ImagesiftBot",
+	"img2dataset",
+	"ISSCyberRiskCrawler",
+	"Kangaroo Bot",
+	"Meta-ExternalAgent",
+	"Meta-ExternalFetcher",
+	"OAI-SearchBot",
+	"magpie-crawler",
+	"Meltwater",
+	"msnbot-media",
+	"omgili",
+	"omgilibot",
+	"PanguBot",
+	"peer39_crawler",
+	"PerplexityBot",
+	"PetalBot",
+	"PiplBot",
+	"Scrapy",
+	"Seekr",
+	"Sidetrade indexer bot",
+	"scoop.it",
+	"Timpibot",
+	"VelenPublicWebCrawler",
+	"Webzio-Extended",
+	"yandex",
+	"YouBot"
+];
+
+/// User-Agent patterns left by headless browsers and browser automation tools, which a lot of AI
+/// data collection runs through instead of a crawler with its own name. These aren't blocked by
+/// default; turn them on with [`NoAiLayer::block_headless_browsers`]. They're never put in a
+/// generated robots.txt, since automation tools don't read it.
+///
+/// [`NoAiLayer::block_headless_browsers`]: crate::NoAiLayer::block_headless_browsers
+pub static HEADLESS_AGENTS: &[&str] = &[
+	"HeadlessChrome",
+	"PhantomJS",
+	"Puppeteer",
+	"Playwright",
+	"Selenium",
+	"SlimerJS",
+	"Nightmare"
+];
+
+/// The patterns from [`AI_AGENTS`] (and a few others) of crawlers which collect images rather than
+/// pages, which are answered with the image set with [`NoAiLayer::placeholder_image`] (if there is
+/// one) instead of the usual action
+///
+/// [`NoAiLayer::placeholder_image`]: crate::NoAiLayer::placeholder_image
+pub static IMAGE_AGENTS: &[&str] = &[
+	"Googlebot-Image",
+	"GoogleOther-Image",
+	"ImagesiftBot",
+	"img2dataset",
+	"msnbot-media"
+];
+
+/// The hosts of AI chat products and answer engines, whose links people follow to a site. Requests
+/// with a `Referer` on any of these (or their subdomains) are reported with the host as their
+/// [`Detection::pattern`] when [`NoAiLayer::track_ai_referrals`] is turned on.
+///
+/// [`Detection::pattern`]: crate::Detection::pattern
+/// [`NoAiLayer::track_ai_referrals`]: crate::NoAiLayer::track_ai_referrals
+pub static AI_REFERRERS: &[&str] = &[
+	"chatgpt.com",
+	"chat.openai.com",
+	"perplexity.ai",
+	"gemini.google.com",
+	"bard.google.com",
+	"copilot.microsoft.com",
+	"claude.ai",
+	"chat.deepseek.com",
+	"chat.mistral.ai",
+	"poe.com",
+	"you.com",
+	"phind.com"
+];
+
+/// Returns the entry of [`AI_REFERRERS`] that the given `Referer` header comes from, if any
+pub(crate) fn ai_referrer(referer: &HeaderValue) -> Option<&'static str> {
+	let uri = Uri::try_from(referer.as_bytes()).ok()?;
+	let host = uri.host()?.as_bytes();
+	AI_REFERRERS.iter().copied().find(|referrer| {
+		let Some(start) = host.len().checked_sub(referrer.len()) else {
+			return false;
+		};
+		host[start..].eq_ignore_ascii_case(referrer.as_bytes())
+			&& (start == 0 || host[start - 1] == b'.')
+	})
+}
+
+/// The broad purpose a known AI agent serves, which can be used to only block (or disallow in
+/// robots.txt) some kinds of agents, e.g. blocking training crawlers while still allowing AI search
+/// engines to index your site.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum AgentCategory {
+	/// Crawlers which collect content to train AI models on
+	Training,
+	/// Crawlers which index content so that it can be referenced and linked to by AI-powered
+	/// search engines and answer features
+	Search,
+	/// Agents which fetch a page on behalf of a user, e.g. when they ask a chatbot about it
+	Assistant,
+	/// Scrapers, data brokers, and crawlers whose purpose isn't clearly documented. Agents which
+	/// this crate doesn't know about also fall into this category.
+	Scraper,
+	/// People who followed a link to the site from an AI chat product, rather than agents. No
+	/// pattern falls into this category; these are only found by their `Referer`, when
+	/// [`NoAiLayer::track_ai_referrals`] is turned on.
+	///
+	/// [`NoAiLayer::track_ai_referrals`]: crate::NoAiLayer::track_ai_referrals
+	Referral
+}
+
+impl AgentCategory {
+	/// Every category, in the order they're declared in
+	pub const ALL: &'static [Self] = &[
+		Self::Training,
+		Self::Search,
+		Self::Assistant,
+		Self::Scraper,
+		Self::Referral
+	];
+
+	/// Returns the category that the given pattern from [`AI_AGENTS`] falls into. Any pattern not
+	/// in [`AI_AGENTS`] is categorized as [`AgentCategory::Scraper`].
+	pub fn of(agent: &str) -> Self {
+		match agent {
+			"AI2Bot"
+			| "Ai2Bot-Dolma"
+			| "anthropic-ai"
+			| "Applebot-Extended"
+			| "Bytespider"
+			| "CCBot"
+			| "ClaudeBot"
+			| "FacebookBot"
+			| "FriendlyCrawler"
+			| "Google-Extended"
+			| "GPTBot"
+			| "ICC-Crawler"
+			| "img2dataset"
+			| "Kangaroo Bot"
+			| "Meta-ExternalAgent"
+			| "PanguBot"
+			| "Timpibot"
+			| "VelenPublicWebCrawler"
+			| "Webzio-Extended" => Self::Training,
+			"Amazonbot" | "Applebot" | "DuckAssistBot" | "Googlebot-Image" | "OAI-SearchBot"
+			| "msnbot-media" | "PerplexityBot" | "PetalBot" | "yandex" | "YouBot" => Self::Search,
+			"ChatGPT-User"
+			| "Claude-Web"
+			| "cohere-ai"
+			| "iaskspider/2.0"
+			| "Meta-ExternalFetcher" => Self::Assistant,
+			#[cfg(feature = "latest-list")]
+			_ => LATEST_CATEGORIES
+				.iter()
+				.find(|(pattern, _)| *pattern == agent)
+				.map_or(Self::Scraper, |(_, category)| *category),
+			#[cfg(not(feature = "latest-list"))]
+			_ => Self::Scraper
+		}
+	}
+
+	/// Whether agents in this category can be expected to respect robots.txt. Assistants fetch
+	/// pages because a user explicitly asked them to, so they generally don't.
+	pub fn respects_robots_txt(self) -> bool {
+		self != Self::Assistant
+	}
+
+	/// The name of this category in snake_case, as it's written in configs and logs
+	pub(crate) fn name(self) -> &'static str {
+		match self {
+			Self::Training => "training",
+			Self::Search => "search",
+			Self::Assistant => "assistant",
+			Self::Scraper => "scraper",
+			Self::Referral => "referral"
+		}
+	}
+}
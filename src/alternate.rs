@@ -0,0 +1,77 @@
+use std::{
+	future::{Future, Pending},
+	task::{Context, Poll}
+};
+
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{NoAiLayer, NoAiService};
+
+/// A [`NoAiLayer`] with a second service for [`Action::Alternate`] to send detected requests to,
+/// created with [`NoAiLayer::alternate`]
+///
+/// [`Action::Alternate`]: crate::Action::Alternate
+#[derive(Clone)]
+pub struct AlternateLayer<A> {
+	pub(crate) layer: NoAiLayer,
+	pub(crate) alternate: A
+}
+
+impl<S, A: Clone> Layer<S> for AlternateLayer<A> {
+	type Service = NoAiService<S, Alternate<A>>;
+	fn layer(&self, inner: S) -> Self::Service {
+		let service = self.layer.layer(inner);
+		NoAiService {
+			inner: service.inner,
+			alternate: Alternate(self.alternate.clone()),
+			shared: service.shared
+		}
+	}
+}
+
+/// The alternate service of a [`NoAiService`] that was created by an [`AlternateLayer`]
+#[derive(Clone)]
+pub struct Alternate<A>(A);
+
+/// A service that [`Action::Alternate`] can send requests to, if there is one. This is
+/// implemented for [`Alternate`], and for `()` when there isn't one.
+///
+/// [`Action::Alternate`]: crate::Action::Alternate
+pub trait MaybeAlternate<Req, Resp, Err> {
+	/// The future that the service returns
+	type Future: Future<Output = Result<Resp, Err>>;
+
+	/// As in [`Service::poll_ready`]
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Err>>;
+
+	/// Passes `req` on to the service, or returns it if there isn't one
+	fn call(&mut self, req: Req) -> Result<Self::Future, Req>;
+}
+
+impl<Req, Resp, Err> MaybeAlternate<Req, Resp, Err> for () {
+	type Future = Pending<Result<Resp, Err>>;
+
+	fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Err>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn call(&mut self, req: Req) -> Result<Self::Future, Req> {
+		Err(req)
+	}
+}
+
+impl<A, Req> MaybeAlternate<Req, A::Response, A::Error> for Alternate<A>
+where
+	A: Service<Req>
+{
+	type Future = A::Future;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), A::Error>> {
+		self.0.poll_ready(cx)
+	}
+
+	fn call(&mut self, req: Req) -> Result<Self::Future, Req> {
+		Ok(self.0.call(req))
+	}
+}
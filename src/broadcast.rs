@@ -0,0 +1,113 @@
+use std::{net::IpAddr, time::Duration};
+
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+use crate::{Action, AgentCategory, Detection, DetectionSink};
+
+/// A detection made by a [`NoAiLayer`], as sent to the subscribers of a [`DetectionBroadcast`].
+/// This has everything that a [`Detection`] does, but owns it, so that it can be sent to other
+/// tasks.
+///
+/// [`NoAiLayer`]: crate::NoAiLayer
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DetectionEvent {
+	/// When the request was detected, since the unix epoch according to the layer's [`Clock`]
+	///
+	/// [`Clock`]: crate::Clock
+	pub timestamp: Duration,
+	/// As in [`Detection::pattern`]
+	pub pattern: String,
+	/// As in [`Detection::category`]
+	pub category: AgentCategory,
+	/// As in [`Detection::user_agent`]
+	pub user_agent: Vec<u8>,
+	/// As in [`Detection::path`]
+	pub path: String,
+	/// As in [`Detection::non_compliant`]
+	pub non_compliant: bool,
+	/// As in [`Detection::client_ip`]
+	pub client_ip: Option<IpAddr>,
+	/// As in [`Detection::escalated`]
+	pub escalated: bool,
+	/// As in [`Detection::action`]
+	pub action: Option<Action>
+}
+
+/// A [`DetectionSink`] which sends every detection to any number of subscribers over a
+/// [`tokio::sync::broadcast`] channel, so that other tasks (like a dashboard's websocket, an
+/// alerting worker, or something that adds offenders to a firewall) can follow along without the
+/// layer knowing about them. Add it to a layer with [`NoAiLayer::detection_sink`], and keep a clone
+/// of it around to subscribe with.
+///
+/// Detections are sent without ever waiting, and subscribers which fall more than the channel's
+/// capacity behind miss the oldest ones (and are told how many with
+/// [`RecvError::Lagged`](tokio::sync::broadcast::error::RecvError::Lagged)). Detections made
+/// while nothing is subscribed are dropped.
+///
+/// Requires the `broadcast` feature.
+///
+/// ```rust
+/// use tower_no_ai::{DetectionBroadcast, NoAiLayer};
+///
+/// let broadcast = DetectionBroadcast::new(256);
+/// let layer = NoAiLayer::new("https://example.com").detection_sink(broadcast.clone());
+///
+/// let mut detections = broadcast.subscribe();
+/// # async {
+/// while let Ok(detection) = detections.recv().await {
+///     if detection.non_compliant {
+///         eprintln!("{} is ignoring robots.txt", detection.pattern);
+///     }
+/// }
+/// # };
+/// ```
+///
+/// [`NoAiLayer::detection_sink`]: crate::NoAiLayer::detection_sink
+#[derive(Clone, Debug)]
+pub struct DetectionBroadcast {
+	sender: Sender<DetectionEvent>
+}
+
+impl DetectionBroadcast {
+	/// Create a new `Self` whose subscribers can each be up to `capacity` detections behind
+	///
+	/// # Panics
+	///
+	/// If `capacity` is 0
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			sender: broadcast::channel(capacity).0
+		}
+	}
+
+	/// Returns a receiver of every detection made from now on
+	pub fn subscribe(&self) -> Receiver<DetectionEvent> {
+		self.sender.subscribe()
+	}
+
+	/// The number of receivers which are subscribed right now
+	pub fn subscribers(&self) -> usize {
+		self.sender.receiver_count()
+	}
+}
+
+impl DetectionSink for DetectionBroadcast {
+	fn record(&self, detection: &Detection<'_>, since_epoch: Duration) {
+		if self.sender.receiver_count() == 0 {
+			return;
+		}
+		// this can only fail if everything unsubscribed since we checked
+		_ = self.sender.send(DetectionEvent {
+			timestamp: since_epoch,
+			pattern: detection.pattern.to_owned(),
+			category: detection.category,
+			user_agent: detection.user_agent.to_vec(),
+			path: detection.path.to_owned(),
+			non_compliant: detection.non_compliant,
+			client_ip: detection.client_ip,
+			escalated: detection.escalated,
+			action: detection.action.cloned()
+		});
+	}
+}
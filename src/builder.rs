@@ -0,0 +1,172 @@
+use std::{error::Error, fmt};
+
+use bytes::Bytes;
+use http::{uri::InvalidUri, HeaderMap, HeaderName, HeaderValue, Request, Uri};
+
+use crate::{
+	redirect::RedirectTarget, Action, AgentCategory, AsyncCondition, CdnVerdict, ClientIpSource,
+	Clock, DecisionLog, Detection, DetectionSink, FingerprintSource, HeaderHeuristics, MatchMode,
+	Mode, NoAiLayer, NoAiStats, OffenderList, Pressure, RateAnomaly, RefetchQuery, Schedule,
+	Scoring
+};
+
+/// A builder for [`NoAiLayer`]s, created with [`NoAiLayer::builder`], which checks that the
+/// configuration makes sense when it's [built](Self::build) instead of letting it misbehave once
+/// requests start coming in.
+///
+/// Each of the methods on this is the same as the [`NoAiLayer`] method with the same name.
+///
+/// ```rust
+/// use tower_no_ai::{Action, AgentCategory, NoAiLayer};
+///
+/// let layer = NoAiLayer::builder()
+///     .redirect_url("https://fsn1-speed.hetzner.com/10GB.bin")
+///     .block_categories([AgentCategory::Training])
+///     .escalate_to(Action::Garbage(1024 * 1024))
+///     .build()
+///     .unwrap();
+///
+/// assert!(NoAiLayer::builder().redirect_url("not a url").build().is_err());
+/// ```
+#[must_use]
+pub struct NoAiLayerBuilder {
+	redir_url: Option<String>,
+	layer: NoAiLayer
+}
+
+macro_rules! delegate {
+	($(fn $name:ident($($arg:ident: $ty:ty),*);)*) => {
+		$(
+			#[doc = concat!("See [`NoAiLayer::", stringify!($name), "`]")]
+			pub fn $name(mut self, $($arg: $ty),*) -> Self {
+				self.layer = self.layer.$name($($arg),*);
+				self
+			}
+		)*
+	};
+}
+
+impl NoAiLayerBuilder {
+	pub(crate) fn new() -> Self {
+		Self {
+			redir_url: None,
+			layer: NoAiLayer::with_redirect(None)
+		}
+	}
+
+	/// Set the URL that detected agents are redirected to. This must be set, and must be a valid
+	/// [`Uri`], for [`Self::build`] to succeed.
+	pub fn redirect_url(mut self, redir_url: impl Into<String>) -> Self {
+		self.redir_url = Some(redir_url.into());
+		self
+	}
+
+	delegate! {
+		fn force_refetching(force_refetching: bool);
+		fn refetch_param(name: impl Into<String>);
+		fn refetch_query(refetch_query: RefetchQuery);
+		fn block_categories(categories: impl IntoIterator<Item = AgentCategory>);
+		fn add_agents(agents: impl IntoIterator<Item = impl Into<String>>);
+		fn remove_agents(agents: impl IntoIterator<Item = impl Into<String>>);
+		fn allow_agents(agents: impl IntoIterator<Item = impl Into<String>>);
+		fn match_mode(mode: MatchMode, patterns: impl IntoIterator<Item = impl Into<String>>);
+		fn default_match_mode(mode: MatchMode);
+		fn exempt_paths(paths: impl IntoIterator<Item = impl Into<String>>);
+		fn response_headers(headers: HeaderMap);
+		fn block_message(message: impl Into<String>);
+		fn placeholder_image(content_type: HeaderValue, image: impl Into<Bytes>);
+		fn matched_header(matched_header: bool);
+		fn match_header(name: HeaderName, pattern: impl Into<String>);
+		fn block_if(condition: impl Fn(&Request<()>) -> bool + Send + Sync + 'static);
+		fn block_only_if(condition: impl Fn(&Request<()>) -> bool + Send + Sync + 'static);
+		fn block_if_async(condition: AsyncCondition);
+		fn shed_bots_under_load(pressure: Pressure);
+		fn detection_sink(sink: impl DetectionSink);
+		fn decision_log(log: DecisionLog);
+		fn header_heuristics(heuristics: HeaderHeuristics);
+		fn rate_anomaly(rate: RateAnomaly);
+		fn cdn_verdict(verdict: CdnVerdict);
+		fn scoring(scoring: Scoring);
+		fn schedule(schedule: Schedule);
+		fn track_ai_referrals(track: bool);
+		fn ai_referral_action(action: Action);
+		fn block_headless_browsers(block: bool);
+		fn grpc_aware(grpc_aware: bool);
+		fn clock(clock: impl Clock);
+		fn block_fingerprints(
+			source: FingerprintSource,
+			fingerprints: impl IntoIterator<Item = impl Into<String>>
+		);
+		fn mode(mode: Mode);
+		fn disallow_paths(paths: impl IntoIterator<Item = impl Into<String>>);
+		fn stats(stats: NoAiStats);
+		fn on_detection(hook: impl Fn(&Detection<'_>) + Send + Sync + 'static);
+		fn action(action: Action);
+		fn client_ip_source(source: ClientIpSource);
+		fn honeypot_paths(paths: impl IntoIterator<Item = impl Into<String>>);
+		fn escalate_to(action: Action);
+		fn offenders(offenders: OffenderList);
+	}
+
+	#[cfg(feature = "hot-reload")]
+	delegate! {
+		fn agents_file(file: crate::AgentsFile);
+	}
+
+	#[cfg(feature = "maxmind")]
+	delegate! {
+		fn geo_block(geo: crate::GeoBlock);
+	}
+
+	#[cfg(feature = "challenge")]
+	delegate! {
+		fn challenge(challenge: crate::Challenge);
+	}
+
+	#[cfg(feature = "honeytokens")]
+	delegate! {
+		fn honeytokens(honeytokens: crate::Honeytokens);
+	}
+
+	/// Check the configuration and create the [`NoAiLayer`] it describes
+	pub fn build(self) -> Result<NoAiLayer, BuildError> {
+		let redir_url = self.redir_url.ok_or(BuildError::MissingRedirectUrl)?;
+		redir_url
+			.parse::<Uri>()
+			.map_err(BuildError::InvalidRedirectUrl)?;
+
+		let mut layer = self.layer;
+		let shared = layer.shared_mut();
+		shared.redirect = Some(RedirectTarget::new(redir_url, &shared.refetch_param));
+		Ok(layer)
+	}
+}
+
+/// The error returned by [`NoAiLayerBuilder::build`] when the configuration it was given doesn't
+/// make sense
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BuildError {
+	/// [`NoAiLayerBuilder::redirect_url`] was never called
+	MissingRedirectUrl,
+	/// The URL given to [`NoAiLayerBuilder::redirect_url`] isn't a valid [`Uri`]
+	InvalidRedirectUrl(InvalidUri)
+}
+
+impl fmt::Display for BuildError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::MissingRedirectUrl => f.write_str("no redirect url was set"),
+			Self::InvalidRedirectUrl(_) => f.write_str("the redirect url is not a valid uri")
+		}
+	}
+}
+
+impl Error for BuildError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			Self::MissingRedirectUrl => None,
+			Self::InvalidRedirectUrl(e) => Some(e)
+		}
+	}
+}
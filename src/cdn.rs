@@ -0,0 +1,121 @@
+use http::{HeaderMap, HeaderName, HeaderValue};
+
+/// The verdicts of a CDN's own bot detection (like Cloudflare's Bot Management, or Fastly's and
+/// Akamai's bot products), as passed along to this service in request headers, to use alongside
+/// this crate's own detection. Add it to a layer with [`NoAiLayer::cdn_verdict`].
+///
+/// A CDN can say two things about a request:
+/// - that it was [verified](Self::verified_header) to come from the bot that it claims to be. Such
+///   requests are never flagged just for how they look (by their fingerprint, headers, network,
+///   request rate, or the CDN's score), but are still blocked if their User-Agent (or another
+///   header) matches a blocked agent, since a verified AI crawler is still an AI crawler.
+/// - how likely it is to come from a bot, as a [score](Self::block_below). Requests whose score is
+///   below the threshold are reported with a [`Detection::pattern`] of [`Self::PATTERN`], and
+///   like with a [`RateAnomaly`], this can be set to only [add to](Self::score) the score of a
+///   [`HeaderHeuristics`] instead.
+///
+/// Headers can be set by anyone, so this should only be used if every request comes through the
+/// CDN, and the CDN overwrites (or removes) these headers on the requests that it passes along.
+///
+/// ```rust
+/// use tower_no_ai::{CdnVerdict, NoAiLayer};
+///
+/// // with request header transform rules that set `cf-verified-bot` to
+/// // `cf.bot_management.verified_bot` and `cf-bot-score` to `cf.bot_management.score`
+/// let layer = NoAiLayer::new("https://example.com").cdn_verdict(CdnVerdict::cloudflare());
+///
+/// // or for any other CDN
+/// let verdict = CdnVerdict::new()
+///     .verified_header("x-bot-verified".parse().unwrap(), "1".parse().unwrap())
+///     .block_below("x-bot-score".parse().unwrap(), 20);
+/// let layer = NoAiLayer::new("https://example.com").cdn_verdict(verdict);
+/// ```
+///
+/// [`NoAiLayer::cdn_verdict`]: crate::NoAiLayer::cdn_verdict
+/// [`Detection::pattern`]: crate::Detection::pattern
+/// [`RateAnomaly`]: crate::RateAnomaly
+/// [`HeaderHeuristics`]: crate::HeaderHeuristics
+#[derive(Clone, Debug, Default)]
+pub struct CdnVerdict {
+	verified: Vec<(HeaderName, HeaderValue)>,
+	// the header and the score below which requests are flagged
+	bot_score: Option<(HeaderName, u32)>,
+	pub(crate) score: Option<u32>
+}
+
+impl CdnVerdict {
+	/// The [`Detection::pattern`] that requests flagged by their CDN's score are reported with
+	///
+	/// [`Detection::pattern`]: crate::Detection::pattern
+	pub const PATTERN: &'static str = "cdn bot score";
+
+	/// Create a new `Self` which doesn't read any headers yet
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Create a new `Self` for Cloudflare, which treats requests with `cf-verified-bot: true` as
+	/// verified, and flags those with a `cf-bot-score` below 30 (which Cloudflare considers likely
+	/// to be automated). Cloudflare doesn't send either of these on its own; they have to be added
+	/// with request header transform rules (from `cf.bot_management.verified_bot` and
+	/// `cf.bot_management.score`).
+	pub fn cloudflare() -> Self {
+		Self::new()
+			.verified_header(
+				HeaderName::from_static("cf-verified-bot"),
+				HeaderValue::from_static("true")
+			)
+			.block_below(HeaderName::from_static("cf-bot-score"), 30)
+	}
+
+	/// Treat requests as verified if they have the header `name` set to `value` (ignoring case).
+	/// This can be called more than once, for a request to be verified if it has any of them.
+	#[must_use]
+	pub fn verified_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+		self.verified.push((name, value));
+		self
+	}
+
+	/// Flag requests whose header `name` holds a score (as a whole number, where lower is more
+	/// likely to be a bot) below `score`. Requests which don't have the header, or whose header
+	/// isn't a number, aren't flagged.
+	#[must_use]
+	pub fn block_below(mut self, name: HeaderName, score: u32) -> Self {
+		self.bot_score = Some((name, score));
+		self
+	}
+
+	/// Instead of blocking flagged requests outright, add `points` to their [`HeaderHeuristics`]
+	/// score, so that they're blocked if that's enough to reach its threshold. This has no effect
+	/// unless the layer also has [`NoAiLayer::header_heuristics`] set.
+	///
+	/// [`HeaderHeuristics`]: crate::HeaderHeuristics
+	/// [`NoAiLayer::header_heuristics`]: crate::NoAiLayer::header_heuristics
+	#[must_use]
+	pub fn score(mut self, points: u32) -> Self {
+		self.score = Some(points);
+		self
+	}
+
+	/// Whether the CDN verified that the request with these headers came from the bot it claims
+	/// to be
+	pub(crate) fn verified(&self, headers: &HeaderMap) -> bool {
+		self.verified.iter().any(|(name, value)| {
+			headers
+				.get_all(name)
+				.iter()
+				.any(|found| found.as_bytes().eq_ignore_ascii_case(value.as_bytes()))
+		})
+	}
+
+	/// Whether the CDN's score for the request with these headers is below the threshold
+	pub(crate) fn flags(&self, headers: &HeaderMap) -> bool {
+		self.bot_score.as_ref().is_some_and(|(name, below)| {
+			headers
+				.get(name)
+				.and_then(|score| score.to_str().ok())
+				.and_then(|score| score.trim().parse::<u32>().ok())
+				.is_some_and(|score| score < *below)
+		})
+	}
+}
@@ -0,0 +1,322 @@
+use std::{fmt, net::IpAddr, sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
+use http::{
+	header::{CACHE_CONTROL, CONTENT_TYPE, COOKIE, USER_AGENT},
+	HeaderMap, HeaderValue, Request, Response, StatusCode
+};
+use sha2::{Digest, Sha256};
+
+use crate::{
+	sign::{decode_hex, push_hex, random_key},
+	RequestPredicate
+};
+
+/// The challenge which [`Action::Challenge`] answers requests with: a tiny HTML page whose script
+/// sets a signed cookie and reloads the page. Browsers pass it without their users noticing more
+/// than a flash, and then get through for as long as the cookie lasts, but the simple HTTP clients
+/// that most scrapers are built on don't run scripts, and so never get past it. Set it up with
+/// [`NoAiLayer::challenge`].
+///
+/// Cookies are signed with HMAC-SHA256, and are only valid for the User-Agent that they were issued
+/// to, and (if the layer has a [`ClientIpSource`]) the network that they were issued to: the /24
+/// for IPv4 addresses, and the /64 for IPv6 addresses.
+///
+/// A headless browser can pass the challenge just like any other, so on its own, this is no use
+/// against scrapers which use one. For those, the challenge can also require a
+/// [proof of work](Self::difficulty), which makes scraping at any scale expensive.
+///
+/// The cookie is only sent back over HTTPS, so the challenge can only be passed on sites which are
+/// served over HTTPS (or from `localhost`).
+///
+/// Requires the `challenge` feature.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use tower_no_ai::{Challenge, NoAiLayer};
+///
+/// // every instance of the service needs to use the same key for their cookies to work on each
+/// // other, so this should come from somewhere like an environment variable
+/// let challenge = Challenge::with_key(b"a secret key".to_vec()).ttl(Duration::from_secs(60 * 60));
+///
+/// let layer = NoAiLayer::new("https://example.com").challenge(challenge);
+/// ```
+///
+/// [`Action::Challenge`]: crate::Action::Challenge
+/// [`NoAiLayer::challenge`]: crate::NoAiLayer::challenge
+/// [`ClientIpSource`]: crate::ClientIpSource
+#[derive(Clone)]
+pub struct Challenge {
+	mac: Hmac<Sha256>,
+	ttl: Duration,
+	cookie_name: String,
+	difficulty: u32,
+	pub(crate) exempt: Option<Arc<RequestPredicate>>
+}
+
+impl Challenge {
+	/// Create a new `Self` which signs its cookies with a random key. Cookies issued by one process
+	/// won't be accepted by any other (or after a restart), so [`Self::with_key`] should be used
+	/// instead if the service runs on more than one instance.
+	///
+	/// # Panics
+	///
+	/// If the operating system can't provide any random bytes for the key
+	pub fn new() -> Self {
+		Self::with_key(random_key().to_vec())
+	}
+
+	/// Create a new `Self` which signs its cookies with `key`, which should be at least 32 random
+	/// bytes
+	pub fn with_key(key: impl Into<Vec<u8>>) -> Self {
+		Self {
+			mac: Hmac::new_from_slice(&key.into()).expect("hmac accepts keys of any length"),
+			ttl: Duration::from_secs(24 * 60 * 60),
+			cookie_name: String::from("noai_challenge"),
+			difficulty: 0,
+			exempt: None
+		}
+	}
+
+	/// Set how long a client gets through for after passing the challenge. This is a day by
+	/// default.
+	#[must_use]
+	pub fn ttl(mut self, ttl: Duration) -> Self {
+		self.ttl = ttl;
+		self
+	}
+
+	/// Set the name of the cookie that's set by the challenge, which is `noai_challenge` by default
+	///
+	/// # Panics
+	///
+	/// If `name` is empty, or contains anything other than ASCII letters, digits, `-`, and `_`
+	#[must_use]
+	pub fn cookie_name(mut self, name: impl Into<String>) -> Self {
+		let name = name.into();
+		assert!(
+			!name.is_empty()
+				&& name
+					.bytes()
+					.all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_')),
+			"{name:?} is not a valid challenge cookie name"
+		);
+		self.cookie_name = name;
+		self
+	}
+
+	/// Require clients to find a number which, when added to the end of their cookie, gives it a
+	/// SHA-256 hash that starts with `bits` zero bits, before they get through. On average, this
+	/// takes 2<sup>`bits`</sup> hashes, so each step up doubles the work: at 16 (about 65 thousand
+	/// hashes), a browser takes well under a second, but a scraper which has to do it for each of
+	/// thousands of User-Agents (or networks, if the layer has a [`ClientIpSource`]) is slowed down
+	/// a lot. Checking it only takes one hash.
+	///
+	/// This is 0 (no work) by default.
+	///
+	/// # Panics
+	///
+	/// If `bits` is more than 32, which would take days
+	///
+	/// [`ClientIpSource`]: crate::ClientIpSource
+	#[must_use]
+	pub fn difficulty(mut self, bits: u32) -> Self {
+		assert!(
+			bits <= 32,
+			"a challenge difficulty of {bits} bits is too high"
+		);
+		self.difficulty = bits;
+		self
+	}
+
+	/// Let requests for which `exempt` returns true through without being challenged, e.g. those
+	/// from crawlers that have been verified by reverse DNS or by a CDN in front of the service.
+	/// This is only checked for requests which would otherwise be challenged, so a crawler that
+	/// everything else lets through doesn't need to be exempted.
+	///
+	/// ```rust
+	/// use tower_no_ai::Challenge;
+	///
+	/// let challenge = Challenge::new()
+	///     .difficulty(16)
+	///     .exempt(|req| req.headers().contains_key("cf-verified-bot"));
+	/// ```
+	#[must_use]
+	pub fn exempt(mut self, exempt: impl Fn(&Request<()>) -> bool + Send + Sync + 'static) -> Self {
+		self.exempt = Some(Arc::new(exempt));
+		self
+	}
+
+	/// Whether the request with the given headers (from `client_ip`, if it's known) has a cookie
+	/// from passing the challenge which hasn't expired yet
+	pub(crate) fn passed(
+		&self,
+		headers: &HeaderMap,
+		client_ip: Option<IpAddr>,
+		now: Duration
+	) -> bool {
+		let Some(cookie) = find_cookie(headers, &self.cookie_name) else {
+			return false;
+		};
+		let Some(dot) = cookie.iter().position(|b| *b == b'.') else {
+			return false;
+		};
+		let (expires, signed) = (&cookie[..dot], &cookie[dot + 1..]);
+
+		// the proof of work comes after the signature, and covers the whole rest of the cookie
+		let signature = if self.difficulty == 0 {
+			signed
+		} else {
+			let Some(dot) = signed.iter().position(|b| *b == b'.') else {
+				return false;
+			};
+			let digest = Sha256::digest(cookie);
+			if leading_zeros(&digest) < self.difficulty {
+				return false;
+			}
+			&signed[..dot]
+		};
+
+		let Some(expires_at) = std::str::from_utf8(expires)
+			.ok()
+			.and_then(|expires| expires.parse::<u64>().ok())
+		else {
+			return false;
+		};
+		let Some(signature) = decode_hex(signature) else {
+			return false;
+		};
+
+		expires_at > now.as_secs()
+			&& self
+				.signer(expires, headers.get(USER_AGENT), client_ip)
+				.verify_slice(&signature)
+				.is_ok()
+	}
+
+	/// Builds the challenge page for a client with the given User-Agent and address
+	pub(crate) fn respond<RespBody: From<Bytes>>(
+		&self,
+		user_agent: Option<&HeaderValue>,
+		client_ip: Option<IpAddr>,
+		now: Duration
+	) -> Response<RespBody> {
+		let mut expires = itoa::Buffer::new();
+		let expires = expires.format(now.saturating_add(self.ttl).as_secs());
+		let signature = self
+			.signer(expires.as_bytes(), user_agent, client_ip)
+			.finalize()
+			.into_bytes();
+
+		let mut token = String::with_capacity(expires.len() + 1 + signature.len() * 2);
+		token.push_str(expires);
+		token.push('.');
+		push_hex(&mut token, &signature);
+
+		let set_cookie = format!(
+			"document.cookie=\"{}=\"+t+\"; Max-Age={}; Path=/; SameSite=Lax; Secure\";\
+			 location.replace(location.href)",
+			self.cookie_name,
+			self.ttl.as_secs()
+		);
+		let script = if self.difficulty == 0 {
+			format!("const t=\"{token}\";{set_cookie}")
+		} else {
+			// counts up from 0 until `t` (the token followed by a dot and the count) hashes to
+			// enough leading zero bits
+			format!(
+				"(async()=>{{const e=new TextEncoder();for(let n=0;;n++){{const \
+				 t=\"{token}.\"+n;const h=new Uint8Array(await \
+				 crypto.subtle.digest(\"SHA-256\",e.encode(t)));let z=0;for(const b of \
+				 h){{if(b){{z+=Math.clz32(b)-24;break}}z+=8}}if(z>={}){{{set_cookie};\
+				 return}}}}}})()",
+				self.difficulty
+			)
+		};
+
+		let page = format!(
+			"<!DOCTYPE html><html><head><meta charset=\"utf-8\"><meta name=\"robots\" \
+			 content=\"noindex\"><title>Checking your browser</title></head><body><noscript>This \
+			 site needs JavaScript to make sure that you're not a bot.</noscript><script>{script}\
+			 </script></body></html>"
+		);
+
+		Response::builder()
+			.status(StatusCode::FORBIDDEN)
+			.header(CONTENT_TYPE, "text/html; charset=utf-8")
+			.header(CACHE_CONTROL, "no-store")
+			.body(RespBody::from(Bytes::from(page)))
+			.unwrap()
+	}
+
+	/// Returns the HMAC of a cookie that expires at `expires` for `user_agent` on the network of
+	/// `client_ip`, waiting to be finished or verified
+	fn signer(
+		&self,
+		expires: &[u8],
+		user_agent: Option<&HeaderValue>,
+		client_ip: Option<IpAddr>
+	) -> Hmac<Sha256> {
+		let mut mac = self.mac.clone();
+		mac.update(expires);
+		mac.update(b".");
+		// the network comes before the user agent, and is tagged with its length, so that the two
+		// can't run into each other
+		match client_ip {
+			Some(IpAddr::V4(ip)) => {
+				let [a, b, c, _] = ip.octets();
+				mac.update(&[4, a, b, c]);
+			}
+			Some(IpAddr::V6(ip)) => {
+				mac.update(&[6]);
+				mac.update(&ip.octets()[..8]);
+			}
+			None => mac.update(&[0])
+		}
+		mac.update(user_agent.map_or(&[], HeaderValue::as_bytes));
+		mac
+	}
+}
+
+impl Default for Challenge {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl fmt::Debug for Challenge {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Challenge")
+			.field("ttl", &self.ttl)
+			.field("cookie_name", &self.cookie_name)
+			.field("difficulty", &self.difficulty)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Returns the value of the cookie with the given name, if the request has one
+fn find_cookie<'h>(headers: &'h HeaderMap, name: &str) -> Option<&'h [u8]> {
+	headers
+		.get_all(COOKIE)
+		.iter()
+		.flat_map(|cookies| cookies.as_bytes().split(|b| *b == b';'))
+		.find_map(|cookie| {
+			let start = cookie.iter().position(|b| *b != b' ')?;
+			cookie[start..]
+				.strip_prefix(name.as_bytes())?
+				.strip_prefix(b"=")
+		})
+}
+
+fn leading_zeros(digest: &[u8]) -> u32 {
+	let mut zeros = 0;
+	for byte in digest {
+		zeros += byte.leading_zeros();
+		if *byte != 0 {
+			break;
+		}
+	}
+	zeros
+}
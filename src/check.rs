@@ -0,0 +1,169 @@
+use std::{
+	fmt,
+	future::Future,
+	net::IpAddr,
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+	time::Duration
+};
+
+use bytes::Bytes;
+use http::{HeaderValue, Request, Response, Uri};
+
+use crate::{Action, AgentCategory, Detection, NoAiShared};
+
+pub(crate) type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+type Condition = dyn Fn(&Request<()>) -> BoxFuture<bool> + Send + Sync;
+pub(crate) type Sleep = dyn Fn(Duration) -> BoxFuture<()> + Send + Sync;
+
+/// A condition for [`NoAiLayer::block_if_async`] which needs to wait on something to decide
+/// whether to block a request, e.g. looking the client up in Redis or asking an external API how
+/// likely it is to be a bot.
+///
+/// A slow lookup can't hold requests up for longer than the condition's timeout, after which
+/// they're decided with [`Self::on_timeout`] instead. There's no timer built in (since this crate
+/// doesn't depend on any async runtime), so the condition needs to be given a function which
+/// sleeps for a duration, such as `tokio::time::sleep`.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use tower_no_ai::{AsyncCondition, NoAiLayer};
+///
+/// # async fn sleep(_: Duration) {}
+/// # async fn lookup_reputation(_: Option<String>) -> u32 { 0 }
+/// let condition = AsyncCondition::new(
+///     |req| {
+///         // the future can't borrow the request, so take what it needs out of it first
+///         let client = req
+///             .headers()
+///             .get("x-real-ip")
+///             .and_then(|ip| ip.to_str().ok())
+///             .map(String::from);
+///         async move { lookup_reputation(client).await > 90 }
+///     },
+///     Duration::from_millis(50),
+///     sleep
+/// );
+///
+/// let layer = NoAiLayer::new("https://example.com").block_if_async(condition);
+/// ```
+///
+/// [`NoAiLayer::block_if_async`]: crate::NoAiLayer::block_if_async
+#[derive(Clone)]
+pub struct AsyncCondition {
+	condition: Arc<Condition>,
+	timeout: Duration,
+	sleep: Arc<Sleep>,
+	on_timeout: bool
+}
+
+impl AsyncCondition {
+	/// Create a new `Self` which blocks the requests for which the future returned by `condition`
+	/// resolves to true. `condition` is only given the request's parts, since its body hasn't
+	/// arrived yet.
+	///
+	/// Once `timeout` has passed, the condition stops being waited on, and the request is decided
+	/// with [`Self::on_timeout`] instead. `sleep` is what's used to wait for it, and should return
+	/// a future which completes once the given duration has passed (like `tokio::time::sleep`).
+	pub fn new<C, Fut, S, SleepFut>(condition: C, timeout: Duration, sleep: S) -> Self
+	where
+		C: Fn(&Request<()>) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = bool> + Send + 'static,
+		S: Fn(Duration) -> SleepFut + Send + Sync + 'static,
+		SleepFut: Future<Output = ()> + Send + 'static
+	{
+		Self {
+			condition: Arc::new(move |req| Box::pin(condition(req))),
+			timeout,
+			sleep: Arc::new(move |duration| Box::pin(sleep(duration))),
+			on_timeout: false
+		}
+	}
+
+	/// Set whether requests are blocked when the condition takes too long to decide. By default,
+	/// they're let through, so that a slow or broken lookup doesn't keep real users out.
+	#[must_use]
+	pub fn on_timeout(mut self, block: bool) -> Self {
+		self.on_timeout = block;
+		self
+	}
+
+	pub(crate) fn check(&self, req: &Request<()>) -> PendingCheck {
+		PendingCheck {
+			condition: (self.condition)(req),
+			timeout: (self.sleep)(self.timeout),
+			on_timeout: self.on_timeout
+		}
+	}
+}
+
+impl fmt::Debug for AsyncCondition {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("AsyncCondition")
+			.field("timeout", &self.timeout)
+			.field("on_timeout", &self.on_timeout)
+			.finish_non_exhaustive()
+	}
+}
+
+/// A running [`AsyncCondition`], which resolves to whether the request should be blocked
+pub struct PendingCheck {
+	condition: BoxFuture<bool>,
+	timeout: BoxFuture<()>,
+	on_timeout: bool
+}
+
+impl Future for PendingCheck {
+	type Output = bool;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<bool> {
+		if let Poll::Ready(block) = self.condition.as_mut().poll(cx) {
+			return Poll::Ready(block);
+		}
+
+		let on_timeout = self.on_timeout;
+		self.timeout.as_mut().poll(cx).map(|()| on_timeout)
+	}
+}
+
+/// What's needed to report (and possibly block) a request once its [`PendingCheck`] has decided
+/// that it should be blocked
+pub struct PendingBlock {
+	pub(crate) shared: Arc<NoAiShared>,
+	// `None` if the request should only be reported, because of the layer's mode
+	pub(crate) action: Option<Action>,
+	pub(crate) grpc: bool,
+	pub(crate) uri: Uri,
+	pub(crate) user_agent: Option<HeaderValue>,
+	pub(crate) client_ip: Option<IpAddr>
+}
+
+impl PendingBlock {
+	/// Reports the request as detected, and returns the response to block it with if it should be
+	pub(crate) fn finish<RespBody: From<Bytes>>(self) -> Option<Response<RespBody>> {
+		self.shared.report(&Detection {
+			pattern: Detection::BLOCK_IF_ASYNC_PATTERN,
+			category: AgentCategory::Scraper,
+			user_agent: self.user_agent.as_ref().map_or(&[], HeaderValue::as_bytes),
+			path: self.uri.path(),
+			non_compliant: false,
+			client_ip: self.client_ip,
+			escalated: false,
+			action: self.action.as_ref()
+		});
+
+		let matched = (Detection::BLOCK_IF_ASYNC_PATTERN, AgentCategory::Scraper);
+		self.action.map(|action| {
+			self.shared.blocked_response(
+				self.grpc,
+				&self.uri,
+				self.user_agent.as_ref(),
+				self.client_ip,
+				&action,
+				Some(matched)
+			)
+		})
+	}
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use crate::{Action, AgentCategory, MatchMode, Mode, NoAiLayer, RefetchQuery};
+
+/// All the commonly-changed settings of a [`NoAiLayer`] in one plain struct, for when you'd rather
+/// drive it from a configuration file or the environment than from code. With the `serde` feature
+/// enabled, this can be deserialized from any format serde supports; every field is optional, and
+/// takes the same default as the corresponding [`NoAiLayer`] method.
+///
+/// ```rust
+/// # #[cfg(feature = "serde")] {
+/// use tower_no_ai::{NoAiConfig, NoAiLayer};
+///
+/// let config: NoAiConfig = toml::from_str(r#"
+///     redirect_url = "https://example.com"
+///     action = { status = 403 }
+///     categories = ["training", "scraper"]
+///     remove_agents = ["Applebot"]
+///     exempt_paths = ["/robots.txt", "/health"]
+///     mode = "shadow"
+/// "#).unwrap();
+///
+/// let layer = NoAiLayer::from_config(config);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+#[non_exhaustive]
+pub struct NoAiConfig {
+	/// The URL to redirect to, as passed to [`NoAiLayer::new`]
+	pub redirect_url: String,
+	/// See [`NoAiLayer::force_refetching`]
+	pub force_refetching: bool,
+	/// See [`NoAiLayer::refetch_param`]
+	pub refetch_param: Option<String>,
+	/// See [`NoAiLayer::refetch_query`]
+	pub refetch_query: RefetchQuery,
+	/// See [`NoAiLayer::action`]
+	pub action: Action,
+	/// See [`NoAiLayer::block_message`]
+	pub block_message: Option<String>,
+	/// The categories to block, as passed to [`NoAiLayer::block_categories`]. If this isn't set,
+	/// every category is blocked.
+	pub categories: Option<Vec<AgentCategory>>,
+	/// See [`NoAiLayer::add_agents`]
+	pub add_agents: Vec<String>,
+	/// See [`NoAiLayer::remove_agents`]
+	pub remove_agents: Vec<String>,
+	/// See [`NoAiLayer::allow_agents`]
+	pub allow_agents: Vec<String>,
+	/// See [`NoAiLayer::default_match_mode`]
+	pub default_match_mode: MatchMode,
+	/// The [`MatchMode`] of each pattern, as set with [`NoAiLayer::match_mode`]
+	pub match_modes: HashMap<String, MatchMode>,
+	/// See [`NoAiLayer::exempt_paths`]
+	pub exempt_paths: Vec<String>,
+	/// See [`NoAiLayer::mode`]
+	pub mode: Mode
+}
+
+impl Default for NoAiConfig {
+	fn default() -> Self {
+		Self {
+			redirect_url: String::new(),
+			force_refetching: true,
+			refetch_param: None,
+			refetch_query: RefetchQuery::Counter,
+			action: Action::Redirect,
+			block_message: None,
+			categories: None,
+			add_agents: Vec::new(),
+			remove_agents: Vec::new(),
+			allow_agents: Vec::new(),
+			default_match_mode: MatchMode::Substring,
+			match_modes: HashMap::new(),
+			exempt_paths: Vec::new(),
+			mode: Mode::Enforce
+		}
+	}
+}
+
+impl NoAiLayer {
+	/// Create a new `Self` from the settings in the given config
+	pub fn from_config(config: NoAiConfig) -> Self {
+		let layer = Self::new(config.redirect_url)
+			.force_refetching(config.force_refetching)
+			.refetch_query(config.refetch_query)
+			.action(config.action)
+			.add_agents(config.add_agents)
+			.remove_agents(config.remove_agents)
+			.allow_agents(config.allow_agents)
+			.default_match_mode(config.default_match_mode)
+			.exempt_paths(config.exempt_paths)
+			.mode(config.mode);
+
+		let layer = config
+			.match_modes
+			.into_iter()
+			.fold(layer, |layer, (pattern, mode)| {
+				layer.match_mode(mode, [pattern])
+			});
+
+		let layer = match config.refetch_param {
+			Some(name) => layer.refetch_param(name),
+			None => layer
+		};
+
+		let layer = match config.block_message {
+			Some(message) => layer.block_message(message),
+			None => layer
+		};
+
+		match config.categories {
+			Some(categories) => layer.block_categories(categories),
+			None => layer
+		}
+	}
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn status_code<'de, D>(deserializer: D) -> Result<http::StatusCode, D::Error>
+where
+	D: serde::Deserializer<'de>
+{
+	let code = <u16 as serde::Deserialize>::deserialize(deserializer)?;
+	http::StatusCode::from_u16(code).map_err(serde::de::Error::custom)
+}
@@ -0,0 +1,292 @@
+use std::{
+	convert::Infallible,
+	fmt::Write as _,
+	future::{ready, Ready},
+	sync::Arc,
+	task::{Context, Poll}
+};
+
+use bytes::Bytes;
+use http::{
+	header::{ALLOW, CACHE_CONTROL, CONTENT_TYPE},
+	Method, Request, Response, StatusCode
+};
+use http_body_util::Full;
+use tower_service::Service;
+
+use crate::{
+	watermark::escape_html, AgentCategory, Clock, NoAiStats, OffenderList, SystemClock,
+	BLOCK_HISTORY_DAYS, UNIQUE_IP_DAYS
+};
+
+// how many rows each of the tables has at most
+const ROWS: usize = 20;
+const OFFENDER_ROWS: usize = 100;
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2rem auto;max-width:60rem;\
+	padding:0 1rem;color:#222}h1{font-size:1.5rem}h2{font-size:1.1rem;margin-top:2rem}\
+	table{border-collapse:collapse;width:100%}th,td{text-align:left;padding:.3rem .5rem;\
+	border-bottom:1px solid #ddd}td.n,th.n{text-align:right;font-variant-numeric:tabular-nums}\
+	code{word-break:break-all}.chart{display:flex;align-items:flex-end;gap:2px;height:8rem;\
+	border-bottom:1px solid #999}.chart div{flex:1;background:#c0392b;min-height:1px}\
+	.axis{display:flex;justify-content:space-between;color:#666;font-size:.8rem}\
+	.empty{color:#666}";
+
+/// A [`Service`] which serves a small HTML page of what a [`NoAiStats`] has collected: the agents
+/// that were detected the most, how many requests were blocked on each of the last
+/// [`BLOCK_HISTORY_DAYS`] days, the paths that agents requested the most, and (if it's given an
+/// [`OffenderList`]) the addresses of known offenders. This is for keeping an eye on things
+/// without setting up anything to collect metrics; the page is entirely self-contained, without
+/// any scripts or outside resources.
+///
+/// The page shows client IP addresses, so it should only be mounted somewhere that only the
+/// site's operators can get to (e.g. behind authentication, or on an internal port). Like
+/// [`RobotsTxtService`], responses use [`Full`] as their body type.
+///
+/// ```rust
+/// use axum::Router;
+/// use tower_no_ai::{NoAiLayer, NoAiStats, StatsDashboard};
+///
+/// let stats = NoAiStats::new();
+/// let layer = NoAiLayer::new("https://example.com").stats(stats.clone());
+///
+/// let admin: Router = Router::new().route_service("/noai", StatsDashboard::new(stats));
+/// ```
+///
+/// [`RobotsTxtService`]: crate::RobotsTxtService
+#[derive(Clone)]
+pub struct StatsDashboard {
+	stats: NoAiStats,
+	offenders: Option<OffenderList>,
+	clock: Arc<dyn Clock>
+}
+
+impl StatsDashboard {
+	/// Create a new `Self` which shows what `stats` has collected
+	pub fn new(stats: NoAiStats) -> Self {
+		Self {
+			stats,
+			offenders: None,
+			clock: Arc::new(SystemClock)
+		}
+	}
+
+	/// Also list the addresses in `offenders`, which should be the list that the layer was given
+	/// with [`NoAiLayer::offenders`]
+	///
+	/// [`NoAiLayer::offenders`]: crate::NoAiLayer::offenders
+	#[must_use]
+	pub fn offenders(mut self, offenders: OffenderList) -> Self {
+		self.offenders = Some(offenders);
+		self
+	}
+
+	/// Tell the time with the given clock instead of the system's, which should be the one that
+	/// the layer was given with [`NoAiLayer::clock`], so that the same offenders have expired. See
+	/// [`Clock`] for more.
+	///
+	/// [`NoAiLayer::clock`]: crate::NoAiLayer::clock
+	#[must_use]
+	pub fn clock(mut self, clock: impl Clock) -> Self {
+		self.clock = Arc::new(clock);
+		self
+	}
+
+	fn render(&self) -> String {
+		let mut page = String::with_capacity(16 * 1024);
+		_ = write!(
+			page,
+			"<!DOCTYPE html><html><head><meta charset=\"utf-8\"><meta name=\"robots\" \
+			 content=\"noindex\"><title>AI agents</title><style>{STYLE}</style></head><body>\
+			 <h1>AI agents</h1>"
+		);
+
+		let agents = self.stats.agents();
+		let (detections, non_compliant) = agents.iter().fold((0, 0), |(d, n), (_, stats)| {
+			(d + stats.detections, n + stats.non_compliant)
+		});
+		_ = write!(
+			page,
+			"<p>{detections} detections of {} agents, {non_compliant} of which ignored \
+			 robots.txt</p>",
+			agents.len()
+		);
+
+		page.push_str("<h2>Top agents</h2>");
+		table(
+			&mut page,
+			2,
+			&[
+				"Agent",
+				"Category",
+				"Detections",
+				"Ignoring robots.txt",
+				&format!("Addresses ({UNIQUE_IP_DAYS} days)")
+			],
+			agents.iter().take(ROWS).map(|(agent, stats)| {
+				[
+					escape_html(agent),
+					String::from(AgentCategory::of(agent).name()),
+					stats.detections.to_string(),
+					stats.non_compliant.to_string(),
+					stats.unique_ips.to_string()
+				]
+			})
+		);
+
+		_ = write!(
+			page,
+			"<h2>Blocks over the last {BLOCK_HISTORY_DAYS} days</h2>"
+		);
+		self.chart(&mut page);
+
+		page.push_str("<h2>Top paths</h2>");
+		table(
+			&mut page,
+			1,
+			&["Path", "Requests"],
+			self.stats.top_paths(ROWS).into_iter().map(|(path, count)| {
+				[
+					format!("<code>{}</code>", escape_html(&path)),
+					count.to_string()
+				]
+			})
+		);
+
+		if let Some(offenders) = &self.offenders {
+			let offenders = offenders.offenders(self.clock.since_epoch());
+			_ = write!(page, "<h2>Offenders ({})</h2>", offenders.len());
+			table(
+				&mut page,
+				1,
+				&["Address"],
+				offenders
+					.iter()
+					.take(OFFENDER_ROWS)
+					.map(|ip| [format!("<code>{ip}</code>")])
+			);
+		}
+
+		let referrals = self.stats.referrals();
+		if !referrals.is_empty() {
+			page.push_str("<h2>Referrals from AI chat products</h2>");
+			table(
+				&mut page,
+				1,
+				&["Referrer", "Visits"],
+				referrals
+					.iter()
+					.take(ROWS)
+					.map(|(referrer, stats)| [escape_html(referrer), stats.detections.to_string()])
+			);
+		}
+
+		page.push_str("</body></html>");
+		page
+	}
+
+	/// Writes a bar chart of the blocks on each day, ending on the latest day that had any
+	fn chart(&self, page: &mut String) {
+		let blocks = self.stats.blocks_by_day();
+		let Some(&(last, _)) = blocks.last() else {
+			page.push_str("<p class=\"empty\">Nothing yet</p>");
+			return;
+		};
+		let first = last.saturating_sub(BLOCK_HISTORY_DAYS as u64 - 1);
+		let max = blocks.iter().map(|(_, count)| *count).max().unwrap_or(1);
+
+		page.push_str("<div class=\"chart\">");
+		for day in first..=last {
+			let count = blocks
+				.iter()
+				.find(|(d, _)| *d == day)
+				.map_or(0, |(_, count)| *count);
+			_ = write!(
+				page,
+				"<div style=\"height:{}%\" title=\"{}: {count}\"></div>",
+				count * 100 / max,
+				date(day)
+			);
+		}
+		_ = write!(
+			page,
+			"</div><div class=\"axis\"><span>{}</span><span>{}</span></div>",
+			date(first),
+			date(last)
+		);
+	}
+}
+
+impl<ReqBody> Service<Request<ReqBody>> for StatsDashboard {
+	type Error = Infallible;
+	type Future = Ready<Result<Self::Response, Self::Error>>;
+	type Response = Response<Full<Bytes>>;
+
+	fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+		if req.method() != Method::GET && req.method() != Method::HEAD {
+			return ready(Ok(Response::builder()
+				.status(StatusCode::METHOD_NOT_ALLOWED)
+				.header(ALLOW, "GET, HEAD")
+				.body(Full::new(Bytes::new()))
+				.unwrap()));
+		}
+
+		ready(Ok(Response::builder()
+			.status(StatusCode::OK)
+			.header(CONTENT_TYPE, "text/html; charset=utf-8")
+			.header(CACHE_CONTROL, "no-store")
+			.body(Full::new(Bytes::from(self.render())))
+			.unwrap()))
+	}
+}
+
+/// Writes a table with the given headings and rows, whose cells are already HTML. Every column
+/// after the first `text` is numeric.
+fn table<const N: usize>(
+	page: &mut String,
+	text: usize,
+	headings: &[&str; N],
+	rows: impl Iterator<Item = [String; N]>
+) {
+	let mut rows = rows.peekable();
+	if rows.peek().is_none() {
+		page.push_str("<p class=\"empty\">Nothing yet</p>");
+		return;
+	}
+
+	page.push_str("<table><tr>");
+	for (i, heading) in headings.iter().enumerate() {
+		let class = if i < text { "" } else { " class=\"n\"" };
+		_ = write!(page, "<th{class}>{heading}</th>");
+	}
+	page.push_str("</tr>");
+	for row in rows {
+		page.push_str("<tr>");
+		for (i, cell) in row.iter().enumerate() {
+			let class = if i < text { "" } else { " class=\"n\"" };
+			_ = write!(page, "<td{class}>{cell}</td>");
+		}
+		page.push_str("</tr>");
+	}
+	page.push_str("</table>");
+}
+
+/// Returns the day that's `days` after the unix epoch as `YYYY-MM-DD`, using the algorithm from
+/// <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn date(days: u64) -> String {
+	let z = days + 719_468;
+	let era = z / 146_097;
+	let day_of_era = z % 146_097;
+	let year_of_era =
+		(day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let mp = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = year_of_era + era * 400 + u64::from(month <= 2);
+	format!("{year:04}-{month:02}-{day:02}")
+}
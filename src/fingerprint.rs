@@ -0,0 +1,55 @@
+use std::collections::HashSet;
+
+use http::{HeaderName, Request};
+
+/// A fingerprint of the TLS handshake that a request arrived over, such as a JA4 or JA3
+/// fingerprint. Whatever terminates TLS in front of the [`NoAiService`] can add this to a request's
+/// extensions for it to be checked with [`FingerprintSource::Extension`].
+///
+/// [`NoAiService`]: crate::NoAiService
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TlsFingerprint(pub String);
+
+/// Where the [`NoAiService`] should look to find the [`TlsFingerprint`] of a request, for
+/// [`NoAiLayer::block_fingerprints`]
+///
+/// Headers can be set by anyone, so only use [`Self::Header`] if there's a proxy in front of this
+/// service which sets that header itself (and removes it from the requests it receives).
+///
+/// [`NoAiService`]: crate::NoAiService
+/// [`NoAiLayer::block_fingerprints`]: crate::NoAiLayer::block_fingerprints
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FingerprintSource {
+	/// A [`TlsFingerprint`] in the request's extensions
+	Extension,
+	/// A header which contains only the fingerprint, such as `X-JA4-Fingerprint`
+	Header(HeaderName)
+}
+
+impl FingerprintSource {
+	pub(crate) fn fingerprint<'r, B>(&self, req: &'r Request<B>) -> Option<&'r str> {
+		match self {
+			Self::Extension => req
+				.extensions()
+				.get::<TlsFingerprint>()
+				.map(|fingerprint| fingerprint.0.as_str()),
+			Self::Header(name) => req.headers().get(name)?.to_str().ok().map(str::trim)
+		}
+	}
+}
+
+/// The fingerprints that a layer blocks, and where it finds them
+#[derive(Clone)]
+pub(crate) struct FingerprintRules {
+	pub(crate) source: FingerprintSource,
+	pub(crate) blocked: HashSet<String>
+}
+
+impl FingerprintRules {
+	/// Returns the blocked fingerprint that the given request has, if it has one
+	pub(crate) fn matching<B>(&self, req: &Request<B>) -> Option<&str> {
+		let fingerprint = self.source.fingerprint(req)?;
+		self.blocked.get(fingerprint).map(String::as_str)
+	}
+}
@@ -0,0 +1,177 @@
+use std::{
+	collections::HashMap,
+	fmt, fs, io,
+	net::IpAddr,
+	path::{Path, PathBuf},
+	sync::{Arc, PoisonError, RwLock}
+};
+
+use maxminddb::{geoip2, Reader};
+
+/// A MaxMind database (`.mmdb` file), such as GeoLite2-ASN or GeoLite2-Country, which is read into
+/// memory for looking up where requests come from with a [`GeoBlock`]
+///
+/// The database isn't re-read on its own, so whatever updates the file (e.g. `geoipupdate`) should
+/// be followed by a call to [`Self::reload`]. This is a cheap handle to shared state, so reloading
+/// any clone of it updates every layer that it's been given to.
+///
+/// Requires the `maxmind` feature.
+#[derive(Clone)]
+pub struct GeoDatabase {
+	path: Arc<PathBuf>,
+	reader: Arc<RwLock<Arc<Reader<Vec<u8>>>>>
+}
+
+impl GeoDatabase {
+	/// Read the database at `path`. This fails if the file can't be read, or isn't a valid
+	/// database.
+	pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+		let path = path.into();
+		Ok(Self {
+			reader: Arc::new(RwLock::new(read_database(&path)?)),
+			path: Arc::new(path)
+		})
+	}
+
+	/// Read the database from the file it was opened from again, to pick up any updates to it. If
+	/// this fails, the database from before keeps being used.
+	pub fn reload(&self) -> io::Result<()> {
+		let reader = read_database(&self.path)?;
+		*self.reader.write().unwrap_or_else(PoisonError::into_inner) = reader;
+		Ok(())
+	}
+
+	fn reader(&self) -> Arc<Reader<Vec<u8>>> {
+		Arc::clone(&self.reader.read().unwrap_or_else(PoisonError::into_inner))
+	}
+}
+
+impl fmt::Debug for GeoDatabase {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("GeoDatabase")
+			.field("path", &self.path)
+			.finish_non_exhaustive()
+	}
+}
+
+fn read_database(path: &Path) -> io::Result<Arc<Reader<Vec<u8>>>> {
+	let reader = Reader::from_source(fs::read(path)?)
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+	Ok(Arc::new(reader))
+}
+
+/// A set of networks (autonomous systems and countries) to block requests from, looked up in
+/// [`GeoDatabase`]s by the client's IP address. Add it to a layer with [`NoAiLayer::geo_block`].
+///
+/// This is meant for the networks that crawlers are run from, like the ASNs of cloud providers,
+/// which real users rarely browse from. Since some of them do, a `GeoBlock` can be set to only
+/// [add to](Self::score) the score of a [`HeaderHeuristics`] instead of blocking outright, so that
+/// requests from these networks are only blocked when their headers also look unlike a browser's.
+///
+/// Requests are only looked up if their client IP is known (see [`ClientIpSource`]), and blocked
+/// ones are reported with a [`Detection::pattern`] of the ASN (e.g. `AS16509`) or `country `
+/// followed by the country's ISO code (e.g. `country XX`).
+///
+/// Requires the `maxmind` feature.
+///
+/// ```rust,no_run
+/// use tower_no_ai::{ClientIpSource, GeoBlock, GeoDatabase, NoAiLayer};
+///
+/// let asns = GeoDatabase::open("/var/lib/GeoIP/GeoLite2-ASN.mmdb").unwrap();
+/// let geo = GeoBlock::new().database(asns.clone()).block_asns([16509, 14618]);
+///
+/// let layer = NoAiLayer::new("https://example.com")
+///     .client_ip_source(ClientIpSource::XRealIp)
+///     .geo_block(geo);
+///
+/// // ...and after the database has been updated
+/// asns.reload().unwrap();
+/// ```
+///
+/// [`NoAiLayer::geo_block`]: crate::NoAiLayer::geo_block
+/// [`HeaderHeuristics`]: crate::HeaderHeuristics
+/// [`ClientIpSource`]: crate::ClientIpSource
+/// [`Detection::pattern`]: crate::Detection::pattern
+#[derive(Clone, Debug, Default)]
+pub struct GeoBlock {
+	databases: Vec<GeoDatabase>,
+	// each blocked network, along with the pattern that it's reported with
+	asns: HashMap<u32, String>,
+	countries: HashMap<String, String>,
+	pub(crate) score: Option<u32>
+}
+
+impl GeoBlock {
+	/// Create a new `Self` with no databases and nothing blocked yet
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Also look requests up in `database`. Each database is checked for both the ASN and the
+	/// country, so these can be separate databases or a single one that has both.
+	#[must_use]
+	pub fn database(mut self, database: GeoDatabase) -> Self {
+		self.databases.push(database);
+		self
+	}
+
+	/// Block requests from any of the autonomous systems with the given numbers
+	#[must_use]
+	pub fn block_asns(mut self, asns: impl IntoIterator<Item = u32>) -> Self {
+		self.asns
+			.extend(asns.into_iter().map(|asn| (asn, format!("AS{asn}"))));
+		self
+	}
+
+	/// Block requests from any of the countries with the given ISO 3166-1 alpha-2 codes (e.g.
+	/// `"XX"`), by where the database says that the address is located
+	#[must_use]
+	pub fn block_countries(
+		mut self,
+		countries: impl IntoIterator<Item = impl Into<String>>
+	) -> Self {
+		self.countries.extend(countries.into_iter().map(|country| {
+			let country = country.into().to_ascii_uppercase();
+			let pattern = format!("country {country}");
+			(country, pattern)
+		}));
+		self
+	}
+
+	/// Instead of blocking requests from these networks outright, add `points` to their
+	/// [`HeaderHeuristics`] score, so that they're blocked if that's enough to reach its threshold.
+	/// This has no effect unless the layer also has [`NoAiLayer::header_heuristics`] set.
+	///
+	/// [`HeaderHeuristics`]: crate::HeaderHeuristics
+	/// [`NoAiLayer::header_heuristics`]: crate::NoAiLayer::header_heuristics
+	#[must_use]
+	pub fn score(mut self, points: u32) -> Self {
+		self.score = Some(points);
+		self
+	}
+
+	/// Looks up `ip`, returning the pattern of the network it's from if it's one of the networks
+	/// to block (or score)
+	pub(crate) fn check(&self, ip: IpAddr) -> Option<&str> {
+		self.databases.iter().find_map(|database| {
+			let reader = database.reader();
+
+			let asn = || {
+				let asn = reader.lookup::<geoip2::Asn>(ip).ok()?;
+				self.asns.get(&asn.autonomous_system_number?)
+			};
+			let country = || {
+				let country = reader.lookup::<geoip2::Country>(ip).ok()?;
+				let code = country.country?.iso_code?;
+				self.countries.get(code)
+			};
+
+			// skip the lookups that can't match anything
+			let network = (!self.asns.is_empty())
+				.then(asn)
+				.flatten()
+				.or_else(|| (!self.countries.is_empty()).then(country).flatten());
+			network.map(String::as_str)
+		})
+	}
+}
@@ -0,0 +1,213 @@
+use std::{
+	borrow::Cow,
+	sync::{Arc, PoisonError, RwLock}
+};
+
+use crate::{
+	matcher::{AgentMatcher, MatchModes},
+	robots::RobotsRules,
+	Action, Mode, NoAiStats, OffenderList
+};
+
+/// The parts of a [`NoAiLayer`]'s configuration which can be changed while it's running, through a
+/// [`NoAiHandle`]. Requests always see one whole version of this, so a change made through a handle
+/// is never observed half-applied.
+///
+/// [`NoAiLayer`]: crate::NoAiLayer
+#[derive(Clone)]
+pub(crate) struct LiveConfig {
+	pub(crate) rules: RobotsRules,
+	// the patterns that `rules` applies to, cached so we don't need to recompute them per-request
+	pub(crate) agents: Vec<Cow<'static, str>>,
+	pub(crate) matcher: AgentMatcher,
+	pub(crate) match_modes: MatchModes,
+	// patterns which are let through even if they match one of `agents`
+	pub(crate) allowed: Vec<String>,
+	pub(crate) mode: Mode,
+	pub(crate) action: Action,
+	pub(crate) escalation: Option<Action>
+}
+
+pub(crate) type SharedConfig = Arc<RwLock<Arc<LiveConfig>>>;
+
+impl LiveConfig {
+	pub(crate) fn new() -> Self {
+		let rules = RobotsRules::new();
+		let agents = rules.agents();
+		let match_modes = MatchModes::default();
+		Self {
+			matcher: AgentMatcher::new(&agents, &match_modes),
+			match_modes,
+			agents,
+			allowed: Vec::new(),
+			rules,
+			mode: Mode::Enforce,
+			action: Action::Redirect,
+			escalation: None
+		}
+	}
+
+	pub(crate) fn add_agents(&mut self, agents: impl IntoIterator<Item = String>) {
+		for agent in agents {
+			self.rules.removed.retain(|removed| *removed != agent);
+			if !self.rules.added.contains(&agent) {
+				self.rules.added.push(agent);
+			}
+		}
+		self.refresh_agents();
+	}
+
+	pub(crate) fn remove_agents(&mut self, agents: impl IntoIterator<Item = String>) {
+		for agent in agents {
+			self.rules.added.retain(|added| *added != agent);
+			if !self.rules.removed.contains(&agent) {
+				self.rules.removed.push(agent);
+			}
+		}
+		self.refresh_agents();
+	}
+
+	pub(crate) fn allow_agents(&mut self, agents: impl IntoIterator<Item = String>) {
+		let agents = agents.into_iter().collect::<Vec<_>>();
+		self.remove_agents(agents.iter().cloned());
+		for agent in agents {
+			if !self.allowed.contains(&agent) {
+				self.allowed.push(agent);
+			}
+		}
+	}
+
+	/// Updates the cached agents to match `rules`, which must be called after any change to them
+	pub(crate) fn refresh_agents(&mut self) {
+		self.agents = self.rules.agents();
+		self.matcher = AgentMatcher::new(&self.agents, &self.match_modes);
+	}
+}
+
+/// Returns the current version of the given config
+pub(crate) fn current(config: &SharedConfig) -> Arc<LiveConfig> {
+	Arc::clone(&config.read().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// Replaces the given config with a copy that's been modified by `f`
+pub(crate) fn update(config: &SharedConfig, f: impl FnOnce(&mut LiveConfig)) {
+	let mut current = config.write().unwrap_or_else(PoisonError::into_inner);
+	let mut new = LiveConfig::clone(&current);
+	f(&mut new);
+	*current = Arc::new(new);
+}
+
+/// A handle for changing the configuration of a [`NoAiLayer`] while it's running, e.g. from an
+/// internal admin endpoint. Get one with [`NoAiLayer::handle`].
+///
+/// Changes made through a handle are seen by the layer it came from, every service that layer has
+/// created (or will create), and every clone of that layer, along with any changes made with
+/// [`NoAiLayer`]'s own methods after the handle was taken. Each change is applied atomically, so
+/// no request ever sees a change half-applied.
+///
+/// ```rust
+/// use tower_no_ai::{Action, Mode, NoAiLayer};
+///
+/// let layer = NoAiLayer::new("https://example.com");
+/// let handle = layer.handle();
+///
+/// // ...later, from an admin endpoint
+/// handle.remove_agents(["Applebot"]);
+/// handle.set_action(Action::Garbage(1024));
+/// handle.set_mode(Mode::Shadow);
+/// handle.flush_offenders();
+/// ```
+///
+/// [`NoAiLayer`]: crate::NoAiLayer
+/// [`NoAiLayer::handle`]: crate::NoAiLayer::handle
+#[derive(Clone)]
+pub struct NoAiHandle {
+	pub(crate) config: SharedConfig,
+	pub(crate) offenders: OffenderList,
+	pub(crate) stats: Option<NoAiStats>
+}
+
+impl NoAiHandle {
+	/// Start blocking the agents with the given patterns, as with [`NoAiLayer::add_agents`]. This
+	/// also undoes any earlier removal of the same patterns.
+	///
+	/// [`NoAiLayer::add_agents`]: crate::NoAiLayer::add_agents
+	pub fn add_agents(&self, agents: impl IntoIterator<Item = impl Into<String>>) {
+		update(&self.config, |config| {
+			let agents = agents.into_iter().map(Into::into).collect::<Vec<String>>();
+			config.allowed.retain(|allowed| !agents.contains(allowed));
+			config.add_agents(agents);
+		});
+	}
+
+	/// Stop blocking the agents with the given patterns, as with [`NoAiLayer::remove_agents`]
+	///
+	/// [`NoAiLayer::remove_agents`]: crate::NoAiLayer::remove_agents
+	pub fn remove_agents(&self, agents: impl IntoIterator<Item = impl Into<String>>) {
+		update(&self.config, |config| {
+			config.remove_agents(agents.into_iter().map(Into::into));
+		});
+	}
+
+	/// Always let through the agents with the given patterns, as with [`NoAiLayer::allow_agents`]
+	///
+	/// [`NoAiLayer::allow_agents`]: crate::NoAiLayer::allow_agents
+	pub fn allow_agents(&self, agents: impl IntoIterator<Item = impl Into<String>>) {
+		update(&self.config, |config| {
+			config.allow_agents(agents.into_iter().map(Into::into));
+		});
+	}
+
+	/// Returns every pattern that's currently being blocked
+	pub fn agents(&self) -> Vec<String> {
+		current(&self.config)
+			.agents
+			.iter()
+			.map(|agent| agent.to_string())
+			.collect()
+	}
+
+	/// Switch between blocking detected agents and just reporting them
+	pub fn set_mode(&self, mode: Mode) {
+		update(&self.config, |config| config.mode = mode);
+	}
+
+	/// Returns the mode that the layer is currently in
+	pub fn mode(&self) -> Mode {
+		current(&self.config).mode
+	}
+
+	/// Change what's done with detected agents, as with [`NoAiLayer::action`]
+	///
+	/// [`NoAiLayer::action`]: crate::NoAiLayer::action
+	pub fn set_action(&self, action: Action) {
+		update(&self.config, |config| config.action = action);
+	}
+
+	/// Change (or, with `None`, turn off) what's done with misbehaving agents, as with
+	/// [`NoAiLayer::escalate_to`]
+	///
+	/// [`NoAiLayer::escalate_to`]: crate::NoAiLayer::escalate_to
+	pub fn set_escalation(&self, escalation: Option<Action>) {
+		update(&self.config, |config| config.escalation = escalation);
+	}
+
+	/// Remove every address from the layer's [`OffenderList`]
+	pub fn flush_offenders(&self) {
+		self.offenders.clear();
+	}
+
+	/// Returns the layer's [`OffenderList`], for finer-grained inspection or editing
+	pub fn offenders(&self) -> &OffenderList {
+		&self.offenders
+	}
+
+	/// Returns the [`NoAiStats`] that the layer records its detections in, if it was given one with
+	/// [`NoAiLayer::stats`], e.g. to see how many addresses an agent has used with
+	/// [`NoAiStats::unique_ips`]
+	///
+	/// [`NoAiLayer::stats`]: crate::NoAiLayer::stats
+	pub fn stats(&self) -> Option<&NoAiStats> {
+		self.stats.as_ref()
+	}
+}
@@ -0,0 +1,108 @@
+use http::{
+	header::{ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE, USER_AGENT},
+	HeaderMap, HeaderName
+};
+
+use crate::contains_bytes;
+
+/// A detector for scrapers which pretend to be browsers (e.g. by rotating through real browsers'
+/// User-Agents), which scores each request on how unlike a real browser's its other headers are.
+/// Add it to a layer with [`NoAiLayer::header_heuristics`].
+///
+/// Requests are scored as follows, and flagged if their score reaches the
+/// [threshold](Self::threshold):
+/// - no `Accept-Language` header, which every mainstream browser sends: 2
+/// - a User-Agent that claims to be a browser, but no `Sec-Fetch-Mode` header, which every modern
+///   browser sends over HTTPS: 2
+/// - no (or an empty) `Accept` header: 1
+/// - a User-Agent that claims to be a browser, loading what its `Sec-Fetch-Mode` header says is a
+///   page (or not saying, without one), with an `Accept` header that doesn't include `text/html`
+///   (like `*/*` on its own, or just `application/json`), which no browser sends when it loads a
+///   page: 1
+/// - no `Accept-Encoding` header: 1
+///
+/// Since browsers only send `Sec-Fetch-*` headers to secure origins, this shouldn't be used on
+/// sites which are served over plain HTTP.
+///
+/// ```rust
+/// use http::HeaderMap;
+/// use tower_no_ai::HeaderHeuristics;
+///
+/// let mut headers = HeaderMap::new();
+/// headers.insert("user-agent", "Mozilla/5.0 (X11; Linux x86_64)".parse().unwrap());
+///
+/// let heuristics = HeaderHeuristics::new();
+/// assert_eq!(heuristics.score(&headers), 6);
+/// assert!(heuristics.flags(&headers));
+///
+/// // a browser asking for anything at all is just as suspicious as one not saying
+/// headers.insert("accept", "*/*".parse().unwrap());
+/// assert_eq!(heuristics.score(&headers), 6);
+/// ```
+///
+/// [`NoAiLayer::header_heuristics`]: crate::NoAiLayer::header_heuristics
+#[derive(Clone, Debug)]
+pub struct HeaderHeuristics {
+	pub(crate) threshold: u32
+}
+
+impl HeaderHeuristics {
+	/// The [`Detection::pattern`] that requests flagged by a `HeaderHeuristics` are reported with
+	///
+	/// [`Detection::pattern`]: crate::Detection::pattern
+	pub const PATTERN: &'static str = "header heuristics";
+
+	/// Create a new `Self` which flags requests with a score of 4 or more
+	pub fn new() -> Self {
+		Self { threshold: 4 }
+	}
+
+	/// Set the score at which requests are flagged
+	#[must_use]
+	pub fn threshold(mut self, threshold: u32) -> Self {
+		self.threshold = threshold;
+		self
+	}
+
+	/// Returns how unlike a real browser's the given request headers are
+	pub fn score(&self, headers: &HeaderMap) -> u32 {
+		let claims_browser = headers
+			.get(USER_AGENT)
+			.is_some_and(|agent| agent.as_bytes().starts_with(b"Mozilla/"));
+
+		let mut score = 0;
+		if !headers.contains_key(ACCEPT_LANGUAGE) {
+			score += 2;
+		}
+		if claims_browser && !headers.contains_key(HeaderName::from_static("sec-fetch-mode")) {
+			score += 2;
+		}
+		match headers.get(ACCEPT) {
+			Some(accept) if !accept.is_empty() => {
+				let navigating = headers
+					.get(HeaderName::from_static("sec-fetch-mode"))
+					.map_or(true, |mode| mode == "navigate");
+				if claims_browser && navigating && !contains_bytes(accept.as_bytes(), b"text/html")
+				{
+					score += 1;
+				}
+			}
+			_ => score += 1
+		}
+		if !headers.contains_key(ACCEPT_ENCODING) {
+			score += 1;
+		}
+		score
+	}
+
+	/// Returns whether the given request headers score high enough to be flagged
+	pub fn flags(&self, headers: &HeaderMap) -> bool {
+		self.score(headers) >= self.threshold
+	}
+}
+
+impl Default for HeaderHeuristics {
+	fn default() -> Self {
+		Self::new()
+	}
+}
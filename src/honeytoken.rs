@@ -0,0 +1,232 @@
+use std::{fmt, net::IpAddr, sync::Arc, time::Duration};
+
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, Key, KeyInit, Nonce};
+use hmac::{Hmac, Mac};
+use http::Uri;
+use sha2::Sha256;
+
+use crate::sign::{decode_hex, push_hex, random_key};
+
+type HitHook = dyn Fn(&HoneytokenHit<'_>) + Send + Sync;
+
+// every token starts with a random nonce of this many bytes
+const NONCE_LEN: usize = 12;
+
+/// URLs which are unique to each page served to a [watermarked](crate::Action::Watermark) agent,
+/// so that when one of them is requested later (e.g. by another crawler which found it in a copy
+/// of the page), it can be traced back to the agent that the page was served to, and when and
+/// where. Add it to a layer with [`NoAiLayer::honeytokens`].
+///
+/// Each URL is the base URL that this was created with followed by a token which holds the agent's
+/// [`Detection::pattern`], its IP address (if it's known), the path of the page that it was served
+/// in, and the time it was served at. Tokens are encrypted (with ChaCha20-Poly1305), so they can't
+/// be read, told apart from any other random-looking URL, or forged by anyone without the key.
+/// Since all of this is in the URL itself, nothing is stored, and the URLs still work after a
+/// restart (or on another instance of the service), as long as they use the same
+/// [key](Self::with_key).
+///
+/// The URLs are put into pages by a [`WatermarkLayer`] with a snippet which contains
+/// `{honeytoken}` (e.g. a hidden link), so that they're only ever given to agents. Requests for
+/// URLs under the base URL are answered with a `404 Not Found`, and the ones with a valid token are
+/// passed to the function given to [`Self::on_hit`] first.
+///
+/// Requires the `honeytokens` feature.
+///
+/// ```rust
+/// use axum::{
+///     response::Html,
+///     routing::{get, Router}
+/// };
+/// use tower_no_ai::{Action, Honeytokens, NoAiLayer, WatermarkLayer};
+///
+/// let honeytokens = Honeytokens::with_key(
+///     "https://example.com/archive/".parse().unwrap(),
+///     b"a secret key".to_vec()
+/// )
+/// .on_hit(|hit| {
+///     eprintln!(
+///         "the copy of {} served to {} was found by {}",
+///         hit.page,
+///         hit.agent,
+///         String::from_utf8_lossy(hit.user_agent)
+///     );
+/// });
+///
+/// let router: Router = Router::new()
+///     .route("/", get(|| async { Html("<body><p>Hello, World!</p></body>") }))
+///     .layer(WatermarkLayer::new([r#"<a href="{honeytoken}">Archive</a>"#]))
+///     .layer(
+///         NoAiLayer::new("https://example.com")
+///             .action(Action::Watermark)
+///             .honeytokens(honeytokens)
+///     );
+/// ```
+///
+/// [`NoAiLayer::honeytokens`]: crate::NoAiLayer::honeytokens
+/// [`Detection::pattern`]: crate::Detection::pattern
+/// [`WatermarkLayer`]: crate::WatermarkLayer
+#[derive(Clone)]
+pub struct Honeytokens {
+	base: String,
+	pub(crate) path: String,
+	cipher: ChaCha20Poly1305,
+	on_hit: Option<Arc<HitHook>>
+}
+
+/// A request for one of the URLs made by [`Honeytokens`], as given to [`Honeytokens::on_hit`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct HoneytokenHit<'a> {
+	/// The [`Detection::pattern`] of the agent that the URL was served to
+	///
+	/// [`Detection::pattern`]: crate::Detection::pattern
+	pub agent: &'a str,
+	/// The IP address of the agent that the URL was served to, if it was known
+	pub served_to: Option<IpAddr>,
+	/// The path of the page that the URL was served in
+	pub page: &'a str,
+	/// When the URL was served, since the unix epoch according to the layer's [`Clock`]
+	///
+	/// [`Clock`]: crate::Clock
+	pub served_at: Duration,
+	/// The request's User-Agent header (which is empty if it didn't have one)
+	pub user_agent: &'a [u8],
+	/// The request's `Referer` header, which says where the URL was found, if the client sent one
+	pub referer: Option<&'a [u8]>,
+	/// The IP address of the client that requested the URL, if a [`ClientIpSource`] was configured
+	/// and it contained an address
+	///
+	/// [`ClientIpSource`]: crate::ClientIpSource
+	pub client_ip: Option<IpAddr>
+}
+
+impl Honeytokens {
+	/// Create a new `Self` whose URLs start with `base` and are encrypted with a random key. URLs
+	/// made by one process won't be recognized by any other (or after a restart), so
+	/// [`Self::with_key`] should be used instead if they need to be traced for longer than that.
+	///
+	/// # Panics
+	///
+	/// If `base` doesn't have a path, since every path under it is taken over, or if the operating
+	/// system can't provide any random bytes for the key
+	pub fn new(base: Uri) -> Self {
+		Self::with_key(base, random_key().to_vec())
+	}
+
+	/// Create a new `Self` whose URLs start with `base` and are encrypted with `key`, which should
+	/// be at least 32 random bytes
+	///
+	/// # Panics
+	///
+	/// If `base` doesn't have a path, since every path under it is taken over
+	pub fn with_key(base: Uri, key: impl Into<Vec<u8>>) -> Self {
+		assert!(
+			base.path() != "/",
+			"honeytokens need a path of their own, not the root of the site"
+		);
+		let (mut base, mut path) = (base.to_string(), base.path().to_owned());
+		for url in [&mut base, &mut path] {
+			if !url.ends_with('/') {
+				url.push('/');
+			}
+		}
+		// the cipher needs a key of exactly 32 bytes, so one is derived from whatever we're given
+		let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&key.into())
+			.expect("hmac accepts keys of any length");
+		mac.update(b"tower-no-ai honeytokens");
+		let key = mac.finalize().into_bytes();
+		Self {
+			base,
+			path,
+			cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+			on_hit: None
+		}
+	}
+
+	/// Call the given function every time one of this `Self`'s URLs is requested. This is called
+	/// synchronously in the request path, so it shouldn't block.
+	#[must_use]
+	pub fn on_hit(mut self, hook: impl Fn(&HoneytokenHit<'_>) + Send + Sync + 'static) -> Self {
+		self.on_hit = Some(Arc::new(hook));
+		self
+	}
+
+	/// Returns a new URL for `agent`, at `client_ip`, to find in `page` at `now`
+	pub(crate) fn url(
+		&self,
+		agent: &str,
+		client_ip: Option<IpAddr>,
+		page: &str,
+		now: Duration
+	) -> String {
+		let ip = client_ip.map(|ip| ip.to_string()).unwrap_or_default();
+		let payload = format!("{}\n{ip}\n{page}\n{agent}", now.as_secs());
+
+		let mut nonce = [0; NONCE_LEN];
+		// without any randomness, there's no nonce that's safe to use, so there's no token either
+		if getrandom::getrandom(&mut nonce).is_err() {
+			return self.base.clone();
+		}
+		let sealed = self
+			.cipher
+			.encrypt(Nonce::from_slice(&nonce), payload.as_bytes())
+			.expect("the payload isn't anywhere near too long to encrypt");
+
+		let mut url = String::with_capacity(self.base.len() + (NONCE_LEN + sealed.len()) * 2);
+		url.push_str(&self.base);
+		push_hex(&mut url, &nonce);
+		push_hex(&mut url, &sealed);
+		url
+	}
+
+	/// Reports a request for `token` (the part of the request's path after the base URL's)
+	/// to [`Self::on_hit`], if it's one that this `Self` made
+	pub(crate) fn record(
+		&self,
+		token: &str,
+		user_agent: &[u8],
+		referer: Option<&[u8]>,
+		client_ip: Option<IpAddr>
+	) {
+		let Some(on_hit) = &self.on_hit else {
+			return;
+		};
+		let Some(token) = decode_hex(token.as_bytes()).filter(|token| token.len() > NONCE_LEN)
+		else {
+			return;
+		};
+		let (nonce, sealed) = token.split_at(NONCE_LEN);
+		// this fails for anything that we didn't make ourselves
+		let Ok(payload) = self.cipher.decrypt(Nonce::from_slice(nonce), sealed) else {
+			return;
+		};
+
+		// we made it, so it's just as we wrote it
+		let Ok(payload) = std::str::from_utf8(&payload) else {
+			return;
+		};
+		let mut fields = payload.splitn(4, '\n');
+		let (Some(served_at), Some(served_to), Some(page), Some(agent)) =
+			(fields.next(), fields.next(), fields.next(), fields.next())
+		else {
+			return;
+		};
+		on_hit(&HoneytokenHit {
+			agent,
+			served_to: served_to.parse().ok(),
+			page,
+			served_at: Duration::from_secs(served_at.parse().unwrap_or_default()),
+			user_agent,
+			referer,
+			client_ip
+		});
+	}
+}
+
+impl fmt::Debug for Honeytokens {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Honeytokens")
+			.field("base", &self.base)
+			.finish_non_exhaustive()
+	}
+}
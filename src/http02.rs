@@ -0,0 +1,292 @@
+use std::{
+	future::Future,
+	net::SocketAddr,
+	pin::Pin,
+	sync::{Arc, Mutex, PoisonError},
+	task::{ready, Context, Poll}
+};
+
+use bytes::Bytes;
+use http::{
+	HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode, Uri, Version
+};
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{MatchedAgent, NoAiLayer, NoAiService, ServiceFut, TlsFingerprint};
+
+/// A [`NoAiLayer`] for services which use the types from version 0.2 of the `http` crate (e.g.
+/// those built on hyper 0.14, axum 0.6, or tower-http 0.4), created with [`NoAiLayer::http02`].
+///
+/// Each request is converted to the `http` 1 types to be checked, and back again to be passed on,
+/// with the same body. Everything from the request's extensions is passed on, but the layer itself
+/// can only see the [`SocketAddr`] (for [`ClientIpSource::ConnectInfo`]) and [`TlsFingerprint`]
+/// in them, which it copies over. The [`MatchedAgent`] that the layer adds to requests and
+/// responses is added to their `http` 0.2 extensions.
+///
+/// The conditions given to the layer (e.g. with [`NoAiLayer::block_if`]) still take `http` 1
+/// requests. Only `NoAiLayer` itself works this way; the other services and layers in this crate
+/// (like [`WatermarkLayer`] and [`NoAiLayer::alternate`]) still need `http` 1 services.
+///
+/// Requires the `http02` feature.
+///
+/// ```rust
+/// use std::convert::Infallible;
+///
+/// use bytes::Bytes;
+/// use tower_layer::Layer;
+/// use tower_no_ai::NoAiLayer;
+/// use tower_service::Service;
+///
+/// #[derive(Clone)]
+/// struct Hello;
+///
+/// impl<B> Service<http02::Request<B>> for Hello {
+///     type Response = http02::Response<Bytes>;
+///     type Error = Infallible;
+///     type Future = std::future::Ready<Result<Self::Response, Infallible>>;
+///
+///     fn poll_ready(
+///         &mut self,
+///         _: &mut std::task::Context<'_>
+///     ) -> std::task::Poll<Result<(), Infallible>> {
+///         std::task::Poll::Ready(Ok(()))
+///     }
+///
+///     fn call(&mut self, _: http02::Request<B>) -> Self::Future {
+///         std::future::ready(Ok(http02::Response::new(Bytes::from_static(b"Hello, World!"))))
+///     }
+/// }
+///
+/// let service = NoAiLayer::new("https://example.com").http02().layer(Hello);
+/// ```
+///
+/// [`SocketAddr`]: std::net::SocketAddr
+/// [`ClientIpSource::ConnectInfo`]: crate::ClientIpSource::ConnectInfo
+/// [`WatermarkLayer`]: crate::WatermarkLayer
+#[derive(Clone)]
+pub struct Http02Layer {
+	pub(crate) layer: NoAiLayer
+}
+
+impl<S> Layer<S> for Http02Layer {
+	type Service = Http02Service<S>;
+	fn layer(&self, inner: S) -> Self::Service {
+		Http02Service {
+			inner: self.layer.layer(Downgrade(inner))
+		}
+	}
+}
+
+/// The service produced by [`Http02Layer`]
+#[derive(Clone)]
+pub struct Http02Service<S> {
+	inner: NoAiService<Downgrade<S>>
+}
+
+/// The service that the [`NoAiService`] inside of an [`Http02Service`] passes requests on to,
+/// which converts them back to the `http` 0.2 types for the service that it wraps
+#[derive(Clone)]
+pub struct Downgrade<S>(S);
+
+// the request's (or response's) own extensions, which can't be converted since they're looked up
+// by type, so they're carried along in the other version's extensions instead. those need to be
+// `Clone`, but these only ever need to be taken out once.
+#[derive(Clone)]
+struct Extensions02(Arc<Mutex<Option<http02::Extensions>>>);
+
+impl Extensions02 {
+	fn new(extensions: http02::Extensions) -> Self {
+		Self(Arc::new(Mutex::new(Some(extensions))))
+	}
+
+	fn take(extensions: &mut http::Extensions) -> http02::Extensions {
+		extensions
+			.remove::<Self>()
+			.and_then(|Self(extensions)| {
+				extensions
+					.lock()
+					.unwrap_or_else(PoisonError::into_inner)
+					.take()
+			})
+			.unwrap_or_default()
+	}
+}
+
+impl<S, ReqBody, RespBody> Service<http02::Request<ReqBody>> for Http02Service<S>
+where
+	S: Service<http02::Request<ReqBody>, Response = http02::Response<RespBody>>,
+	RespBody: From<Bytes>
+{
+	type Error = S::Error;
+	type Future = Http02Fut<ServiceFut<RespBody, S::Error, DowngradeFut<S::Future>>>;
+	type Response = http02::Response<RespBody>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, req: http02::Request<ReqBody>) -> Self::Future {
+		let (parts, body) = req.into_parts();
+		let mut req = Request::new(body);
+		*req.method_mut() = Method::from_bytes(parts.method.as_str().as_bytes())
+			.expect("http 0.2 methods are valid http 1 methods");
+		*req.uri_mut() = Uri::from_maybe_shared(Bytes::from(parts.uri.to_string()))
+			.expect("http 0.2 uris are valid http 1 uris");
+		*req.version_mut() = to_http1_version(parts.version);
+		*req.headers_mut() = to_http1_headers(&parts.headers);
+
+		// the only extensions that the layer looks at
+		if let Some(addr) = parts.extensions.get::<SocketAddr>() {
+			req.extensions_mut().insert(*addr);
+		}
+		if let Some(fingerprint) = parts.extensions.get::<TlsFingerprint>() {
+			req.extensions_mut().insert(fingerprint.clone());
+		}
+		req.extensions_mut()
+			.insert(Extensions02::new(parts.extensions));
+
+		Http02Fut {
+			future: self.inner.call(req)
+		}
+	}
+}
+
+impl<S, ReqBody, RespBody> Service<Request<ReqBody>> for Downgrade<S>
+where
+	S: Service<http02::Request<ReqBody>, Response = http02::Response<RespBody>>
+{
+	type Error = S::Error;
+	type Future = DowngradeFut<S::Future>;
+	type Response = Response<RespBody>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.0.poll_ready(cx)
+	}
+
+	fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+		let (mut parts, body) = req.into_parts();
+		let mut req = http02::Request::new(body);
+		*req.method_mut() = http02::Method::from_bytes(parts.method.as_str().as_bytes())
+			.expect("http 1 methods are valid http 0.2 methods");
+		*req.uri_mut() = http02::Uri::from_maybe_shared(Bytes::from(parts.uri.to_string()))
+			.expect("http 1 uris are valid http 0.2 uris");
+		*req.version_mut() = to_http02_version(parts.version);
+		*req.headers_mut() = to_http02_headers(&parts.headers);
+
+		*req.extensions_mut() = Extensions02::take(&mut parts.extensions);
+		if let Some(matched) = parts.extensions.remove::<MatchedAgent>() {
+			req.extensions_mut().insert(matched);
+		}
+
+		DowngradeFut {
+			future: self.0.call(req)
+		}
+	}
+}
+
+pin_project! {
+	/// The Future type that [`Http02Service::call`] produces
+	pub struct Http02Fut<F> {
+		#[pin]
+		future: F
+	}
+}
+
+impl<F, RespBody, Err> Future for Http02Fut<F>
+where
+	F: Future<Output = Result<Response<RespBody>, Err>>
+{
+	type Output = Result<http02::Response<RespBody>, Err>;
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		let (mut parts, body) = ready!(self.project().future.poll(cx))?.into_parts();
+		let mut response = http02::Response::new(body);
+		*response.status_mut() = http02::StatusCode::from_u16(parts.status.as_u16())
+			.expect("http 1 status codes are valid http 0.2 status codes");
+		*response.version_mut() = to_http02_version(parts.version);
+		*response.headers_mut() = to_http02_headers(&parts.headers);
+
+		*response.extensions_mut() = Extensions02::take(&mut parts.extensions);
+		if let Some(matched) = parts.extensions.remove::<MatchedAgent>() {
+			response.extensions_mut().insert(matched);
+		}
+		Poll::Ready(Ok(response))
+	}
+}
+
+pin_project! {
+	/// The Future type that [`Downgrade::call`] produces
+	pub struct DowngradeFut<F> {
+		#[pin]
+		future: F
+	}
+}
+
+impl<F, RespBody, Err> Future for DowngradeFut<F>
+where
+	F: Future<Output = Result<http02::Response<RespBody>, Err>>
+{
+	type Output = Result<Response<RespBody>, Err>;
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		let (parts, body) = ready!(self.project().future.poll(cx))?.into_parts();
+		let mut response = Response::new(body);
+		*response.status_mut() = StatusCode::from_u16(parts.status.as_u16())
+			.expect("http 0.2 status codes are valid http 1 status codes");
+		*response.version_mut() = to_http1_version(parts.version);
+		*response.headers_mut() = to_http1_headers(&parts.headers);
+		response
+			.extensions_mut()
+			.insert(Extensions02::new(parts.extensions));
+		Poll::Ready(Ok(response))
+	}
+}
+
+const VERSIONS: [(http02::Version, Version); 5] = [
+	(http02::Version::HTTP_09, Version::HTTP_09),
+	(http02::Version::HTTP_10, Version::HTTP_10),
+	(http02::Version::HTTP_11, Version::HTTP_11),
+	(http02::Version::HTTP_2, Version::HTTP_2),
+	(http02::Version::HTTP_3, Version::HTTP_3)
+];
+
+fn to_http1_version(version: http02::Version) -> Version {
+	VERSIONS
+		.iter()
+		.find(|(old, _)| *old == version)
+		.map_or(Version::HTTP_11, |(_, new)| *new)
+}
+
+fn to_http02_version(version: Version) -> http02::Version {
+	VERSIONS
+		.iter()
+		.find(|(_, new)| *new == version)
+		.map_or(http02::Version::HTTP_11, |(old, _)| *old)
+}
+
+fn to_http1_headers(headers: &http02::HeaderMap) -> HeaderMap {
+	let mut converted = HeaderMap::with_capacity(headers.len());
+	for (name, value) in headers {
+		// both versions accept the same names and values
+		if let (Ok(name), Ok(value)) = (
+			HeaderName::from_bytes(name.as_str().as_bytes()),
+			HeaderValue::from_bytes(value.as_bytes())
+		) {
+			converted.append(name, value);
+		}
+	}
+	converted
+}
+
+fn to_http02_headers(headers: &HeaderMap) -> http02::HeaderMap {
+	let mut converted = http02::HeaderMap::with_capacity(headers.len());
+	for (name, value) in headers {
+		if let (Ok(name), Ok(value)) = (
+			http02::HeaderName::from_bytes(name.as_str().as_bytes()),
+			http02::HeaderValue::from_bytes(value.as_bytes())
+		) {
+			converted.append(name, value);
+		}
+	}
+	converted
+}
@@ -0,0 +1,74 @@
+use std::net::{IpAddr, SocketAddr};
+
+use http::{HeaderName, Request};
+
+/// Where the [`NoAiService`] should look to find the IP address of the client that sent a request.
+/// This is needed for anything that keeps track of clients across requests, such as an
+/// [`OffenderList`].
+///
+/// Headers can be set by anyone, so only use a header-based source if there's a proxy in front of
+/// this service which sets (or appends to) that header itself.
+///
+/// [`NoAiService`]: crate::NoAiService
+/// [`OffenderList`]: crate::OffenderList
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ClientIpSource {
+	/// The rightmost address in the `X-Forwarded-For` header, which is the one that was added by
+	/// the proxy closest to this service
+	RightmostXForwardedFor,
+	/// The `X-Real-Ip` header, as set by e.g. nginx
+	XRealIp,
+	/// A header which contains only the client's IP address, such as `CF-Connecting-IP` or
+	/// `Fly-Client-IP`
+	Header(HeaderName),
+	/// The address of the peer that the request's connection came from, for servers which aren't
+	/// behind a proxy. This is read from a [`SocketAddr`] in the request's extensions, or (with
+	/// the `axum` feature) from axum's `ConnectInfo<SocketAddr>`, which is added to every request
+	/// when the router is served with `into_make_service_with_connect_info::<SocketAddr>()`.
+	ConnectInfo
+}
+
+impl ClientIpSource {
+	pub(crate) fn client_ip<B>(&self, req: &Request<B>) -> Option<IpAddr> {
+		let header = match self {
+			Self::ConnectInfo => return peer_addr(req).map(|addr| addr.ip()),
+			Self::RightmostXForwardedFor => {
+				return req
+					.headers()
+					.get_all("x-forwarded-for")
+					.iter()
+					.next_back()?
+					.to_str()
+					.ok()?
+					.rsplit(',')
+					.next()?
+					.trim()
+					.parse()
+					.ok();
+			}
+			Self::XRealIp => "x-real-ip",
+			Self::Header(name) => name.as_str()
+		};
+
+		req.headers()
+			.get(header)?
+			.to_str()
+			.ok()?
+			.trim()
+			.parse()
+			.ok()
+	}
+}
+
+fn peer_addr<B>(req: &Request<B>) -> Option<SocketAddr> {
+	#[cfg(feature = "axum")]
+	if let Some(axum::extract::ConnectInfo(addr)) = req
+		.extensions()
+		.get::<axum::extract::ConnectInfo<SocketAddr>>()
+	{
+		return Some(*addr);
+	}
+
+	req.extensions().get::<SocketAddr>().copied()
+}
@@ -2,195 +2,2031 @@
 #![doc = include_str!("../README.md")]
 
 use std::{
-	future::Future,
+	borrow::Cow,
+	collections::hash_map::RandomState,
+	future::{Future, Pending},
+	hash::{BuildHasher, Hasher},
+	net::IpAddr,
 	pin::Pin,
-	sync::OnceLock,
-	task::{Context, Poll},
-	time::{SystemTime, UNIX_EPOCH}
+	sync::{
+		atomic::{AtomicBool, AtomicU64, Ordering},
+		Arc, OnceLock, RwLock
+	},
+	task::{ready, Context, Poll}
 };
 
-use http::{header::USER_AGENT, Request, Response, StatusCode};
+use bytes::Bytes;
+use http::{
+	header::{CACHE_CONTROL, CONTENT_TYPE, LOCATION, REFERER, USER_AGENT, VARY},
+	HeaderMap, HeaderName, HeaderValue, Request, Response, StatusCode, Uri
+};
+use pin_project_lite::pin_project;
 use tower_layer::Layer;
 use tower_service::Service;
 
-/// The User-Agent patterns checked for and redirected if present
-pub static AI_AGENTS: &[&str] = &[
-	"AI2Bot",
-	"Ai2Bot-Dolma",
-	"AdsBot-Google2",
-	"Amazonbot",
-	"anthropic-ai",
-	"Applebot",
-	"Applebot-Extended",
-	"ArcMobile",
-	"AwarioRssBot",
-	"AwarioSmartBot",
-	"Bytespider",
-	"CCBot",
-	"ChatGPT-User",
-	"Claude-Web",
-	"ClaudeBot",
-	"cohere-ai",
-	"DataForSeoBot",
-	"Diffbot",
-	"DuckAssistBot",
-	"FacebookBot",
-	"FriendlyCrawler",
-	"Google-Extended",
-	"Googlebot-Image",
-	"GoogleOther",
-	"GoogleOther-Image",
-	"GoogleOther-Video",
-	"GPTBot",
-	"iaskspider/2.0",
-	"ICC-Crawler",
-	"ImagesiftBot",
-	"img2dataset",
-	"ISSCyberRiskCrawler",
-	"Kangaroo Bot",
-	"Meta-ExternalAgent",
-	"Meta-ExternalFetcher",
-	"OAI-SearchBot",
-	"magpie-crawler",
-	"Meltwater",
-	"msnbot-media",
-	"omgili",
-	"omgilibot",
-	"PanguBot",
-	"peer39_crawler",
-	"PerplexityBot",
-	"PetalBot",
-	"PiplBot",
-	"Scrapy",
-	"Seekr",
-	"Sidetrade indexer bot",
-	"scoop.it",
-	"Timpibot",
-	"VelenPublicWebCrawler",
-	"Webzio-Extended",
-	"yandex",
-	"YouBot"
-];
-
-/// The service which will redirect the requests with matching user agents
+mod action;
+mod agents;
+mod alternate;
+#[cfg(feature = "broadcast")]
+mod broadcast;
+mod builder;
+mod cdn;
+#[cfg(feature = "challenge")]
+mod challenge;
+mod check;
+mod config;
+mod dashboard;
+mod fingerprint;
+#[cfg(feature = "maxmind")]
+mod geo;
+mod handle;
+mod heuristics;
+#[cfg(feature = "honeytokens")]
+mod honeytoken;
+#[cfg(feature = "http02")]
+mod http02;
+mod ip;
+mod llms;
+mod log;
+mod matcher;
+mod offenders;
+#[cfg(feature = "otel")]
+mod otel;
+mod rate;
+mod redirect;
+#[cfg(feature = "hot-reload")]
+mod reload;
+mod robots;
+mod schedule;
+mod score;
+mod shed;
+#[cfg(any(feature = "challenge", feature = "honeytokens"))]
+mod sign;
+mod signals;
+mod sink;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+mod tarpit;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+mod time;
+mod watermark;
+
+pub use action::Action;
+use agents::ai_referrer;
+pub use agents::{AgentCategory, AI_AGENTS, AI_REFERRERS, HEADLESS_AGENTS, IMAGE_AGENTS};
+pub use alternate::{Alternate, AlternateLayer, MaybeAlternate};
+#[cfg(feature = "broadcast")]
+pub use broadcast::{DetectionBroadcast, DetectionEvent};
+pub use builder::{BuildError, NoAiLayerBuilder};
+pub use cdn::CdnVerdict;
+#[cfg(feature = "challenge")]
+pub use challenge::Challenge;
+pub use check::AsyncCondition;
+use check::{PendingBlock, PendingCheck};
+pub use config::NoAiConfig;
+pub use dashboard::StatsDashboard;
+use fingerprint::FingerprintRules;
+pub use fingerprint::{FingerprintSource, TlsFingerprint};
+#[cfg(feature = "maxmind")]
+pub use geo::{GeoBlock, GeoDatabase};
+pub use handle::NoAiHandle;
+use handle::{LiveConfig, SharedConfig};
+pub use heuristics::HeaderHeuristics;
+#[cfg(feature = "honeytokens")]
+pub use honeytoken::{HoneytokenHit, Honeytokens};
+#[cfg(feature = "http02")]
+pub use http02::{Downgrade, DowngradeFut, Http02Fut, Http02Layer, Http02Service};
+pub use ip::ClientIpSource;
+pub use llms::{LlmsTxtBuilder, LlmsTxtSection};
+pub use log::DecisionLog;
+use matcher::contains_bytes;
+pub use matcher::MatchMode;
+pub use offenders::OffenderList;
+pub use rate::{RateAnomaly, RateKey};
+use redirect::{RedirectTarget, DEFAULT_REFETCH_PARAM};
+#[cfg(feature = "hot-reload")]
+pub use reload::AgentsFile;
+pub use robots::{
+	bot_blocking_robots_txt, RobotsTxtBuilder, RobotsTxtFut, RobotsTxtLayer, RobotsTxtService,
+	ServeRobotsTxt
+};
+pub use schedule::{Schedule, TimeWindow, Weekday};
+pub use score::{Scoring, Signal};
+use score::{Signals, Verdict};
+pub use shed::Pressure;
+use shed::{InFlight, LoadShedding};
+pub use signals::{ContentPolicy, ContentSignals};
+pub use sink::{DetectionSink, RotatingFile};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStats;
+pub use stats::{AgentStats, NoAiStats, BLOCK_HISTORY_DAYS, UNIQUE_IP_DAYS};
+pub use tarpit::{TarpitBody, TarpitFut, TarpitLayer, TarpitService};
+pub use time::{Clock, SystemClock};
+use watermark::Watermarked;
+pub use watermark::{WatermarkBody, WatermarkFut, WatermarkLayer, WatermarkService};
+
+/// Information about a request from an AI agent that a [`NoAiService`] detected, which is passed
+/// to the hook registered with [`NoAiLayer::on_detection`]
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct Detection<'a> {
+	/// The pattern (e.g. from [`AI_AGENTS`]) that matched the request's User-Agent. If it was
+	/// detected by something other than its User-Agent, this says what instead (e.g.
+	/// [`HeaderHeuristics::PATTERN`]).
+	pub pattern: &'a str,
+	/// The category that [`Self::pattern`] falls into
+	pub category: AgentCategory,
+	/// The request's full User-Agent header (which is empty if it didn't have one). This is the raw
+	/// bytes of the header, since it doesn't have to be valid UTF-8 (use
+	/// [`String::from_utf8_lossy`] to display it).
+	pub user_agent: &'a [u8],
+	/// The path that the agent requested
+	pub path: &'a str,
+	/// Whether the requested path is one that the robots.txt generated for the layer (with
+	/// [`NoAiLayer::robots_txt`]) disallows this agent from accessing
+	pub non_compliant: bool,
+	/// The IP address of the client, if a [`ClientIpSource`] was configured and it contained an
+	/// address
+	pub client_ip: Option<IpAddr>,
+	/// Whether this request was answered with the harsher action configured with
+	/// [`NoAiLayer::escalate_to`] instead of the usual one
+	pub escalated: bool,
+	/// What the request is being answered with, or `None` if it's being passed on to the inner
+	/// service (e.g. because of the layer's [`Mode`])
+	pub action: Option<&'a Action>
+}
+
+impl Detection<'_> {
+	/// The [`Detection::pattern`] that requests flagged by the condition given to
+	/// [`NoAiLayer::block_if`] are reported with
+	pub const BLOCK_IF_PATTERN: &'static str = "block_if";
+
+	/// The [`Detection::pattern`] that requests flagged by the [`AsyncCondition`] given to
+	/// [`NoAiLayer::block_if_async`] are reported with
+	pub const BLOCK_IF_ASYNC_PATTERN: &'static str = "block_if_async";
+}
+
+/// Which agent a response from a [`NoAiService`] was blocking. This is added to the extensions of
+/// every response that's sent because of a request's User-Agent, so that outer layers can see
+/// which rule fired.
+///
+/// With [`NoAiLayer::track_ai_referrals`], this is also added to the extensions of requests from
+/// people who were referred by an AI chat product, with [`AgentCategory::Referral`], so that the
+/// inner service can tell them apart.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MatchedAgent {
+	/// The pattern that matched the request's User-Agent, as in [`Detection::pattern`]
+	pub pattern: String,
+	/// The category that [`Self::pattern`] falls into
+	pub category: AgentCategory
+}
+
+/// The service which will redirect the requests with matching user agents. `A` is the service
+/// that [`Action::Alternate`] sends requests to, if the layer was given one with
+/// [`NoAiLayer::alternate`].
 #[derive(Clone)]
-pub struct NoAiService<S> {
+pub struct NoAiService<S, A = ()> {
 	inner: S,
-	layer: NoAiLayer
+	alternate: A,
+	shared: Arc<NoAiShared>
 }
 
-impl<S, ReqBody, RespBody> Service<Request<ReqBody>> for NoAiService<S>
+impl<S, A, ReqBody, RespBody> Service<Request<ReqBody>> for NoAiService<S, A>
 where
 	S: Service<Request<ReqBody>, Response = Response<RespBody>>,
-	S::Future: Send + 'static,
-	RespBody: Default
+	A: MaybeAlternate<Request<ReqBody>, Response<RespBody>, S::Error>,
+	RespBody: From<Bytes>
 {
 	type Error = S::Error;
-	type Future = ServiceFut<RespBody, Self::Error, S::Future>;
+	type Future = ServiceFut<RespBody, Self::Error, S::Future, A::Future>;
 	type Response = Response<RespBody>;
 
 	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-		self.inner.poll_ready(cx)
+		let ready = self.inner.poll_ready(cx);
+		if let (Poll::Pending, Some(shedding)) = (&ready, &self.shared.load_shedding) {
+			shedding.not_ready(&*self.shared.clock);
+		}
+		// we don't know which of the two a request will go to until it's been checked, so both
+		// need to be ready for it
+		ready!(ready)?;
+		self.alternate.poll_ready(cx)
 	}
 
-	fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+	fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+		// if this same layer has already seen this request further out in the stack, it's already
+		// been checked (and counted) once, and anything it would've blocked wouldn't have made it
+		// this far
+		let screened_by = Screened(Arc::as_ptr(&self.shared) as usize);
+		if req.extensions().get::<Screened>() == Some(&screened_by) {
+			return ServiceFut::Inner {
+				future: self.inner.call(req),
+				in_flight: None
+			};
+		}
+		req.extensions_mut().insert(screened_by);
+
+		let path = req.uri().path();
+
+		// honeytokens are only ever handed out to agents, so anyone asking for one found it in
+		// something that an agent took
+		#[cfg(feature = "honeytokens")]
+		if let Some(honeytokens) = &self.shared.honeytokens {
+			if let Some(token) = path.strip_prefix(&*honeytokens.path) {
+				honeytokens.record(
+					token,
+					req.headers()
+						.get(USER_AGENT)
+						.map_or(&[], HeaderValue::as_bytes),
+					req.headers().get(REFERER).map(HeaderValue::as_bytes),
+					self.shared
+						.client_ip_source
+						.as_ref()
+						.and_then(|source| source.client_ip(&req))
+				);
+				return ServiceFut::Respond {
+					response: Some(
+						Response::builder()
+							.status(StatusCode::NOT_FOUND)
+							.body(RespBody::from(Bytes::new()))
+							.unwrap()
+					)
+				};
+			}
+		}
+
+		if self
+			.shared
+			.exempt_paths
+			.iter()
+			.any(|prefix| path.starts_with(&**prefix))
+		{
+			return forward(&mut self.inner, &self.shared, req);
+		}
+
+		// the conditions from `block_if` and `block_only_if`, which default to not adding anything
+		// and not ruling anything out
+		let (req, (custom_block, custom_allow)) =
+			if self.shared.block_if.is_some() || self.shared.block_only_if.is_some() {
+				without_body(req, |req| {
+					(
+						self.shared
+							.block_if
+							.as_ref()
+							.is_some_and(|block_if| block_if(req)),
+						self.shared
+							.block_only_if
+							.as_ref()
+							.map_or(true, |block_only_if| block_only_if(req))
+					)
+				})
+			} else {
+				(req, (false, true))
+			};
+		let scoring = self.shared.scoring();
+		#[cfg(feature = "challenge")]
+		let (req, challenge_exempt) = match &self.shared.challenge.exempt {
+			Some(exempt) if scoring.challenges() => without_body(req, |req| exempt(req)),
+			_ => (req, false)
+		};
+		let path = req.uri().path();
+
+		// everything below here sees this one version of the config, even if it's changed halfway
+		// through by a handle
+		let config = handle::current(&self.shared.config);
+
+		let client_ip = self
+			.shared
+			.client_ip_source
+			.as_ref()
+			.and_then(|source| source.client_ip(&req));
+
+		// the schedule can swap out the usual mode and action for the time being
+		let (mode, action) = match &self.shared.schedule {
+			Some(schedule) =>
+				schedule.current(self.shared.clock.since_epoch(), config.mode, &config.action),
+			None => (config.mode, &config.action)
+		};
+
+		// known offenders get the harsher treatment no matter what they're claiming to be now
+		if let (Some(escalation), Some(ip)) = (&config.escalation, client_ip) {
+			if mode != Mode::Shadow
+				&& self
+					.shared
+					.offenders
+					.contains(ip, self.shared.clock.since_epoch())
+			{
+				return block(
+					&mut self.inner,
+					&mut self.alternate,
+					&self.shared,
+					req,
+					escalation,
+					None,
+					client_ip
+				);
+			}
+		}
+
 		// get the user agent
-		req.headers()
+		let user_agent = req
+			.headers()
 			.get(USER_AGENT)
-			// check if we can actually convert it to a string
-			.and_then(|agent_hdr| agent_hdr.to_str().ok())
-			// and then check that against all of the bad user agents we have stored
-			.is_some_and(|agent| AI_AGENTS.iter().any(|hdr| agent.contains(hdr)))
-			.then(|| -> Self::Future {
-				// if it IS one of the bad user agents, then redirect it to our url and add the
-				// extra query on the end to force refetching if we want that
-				let redir_url = if self.layer.force_refetching {
-					format!(
-						"{}?={}",
-						self.layer.redir_url,
-						SystemTime::now()
-							.duration_since(UNIX_EPOCH)
-							.map_or(0, |d| d.as_nanos())
+			// we match on the raw bytes, since an agent shouldn't be able to sneak past us just by
+			// putting something that isn't ASCII in its user agent
+			.map(HeaderValue::as_bytes);
+
+		// agents that have been explicitly allowed get through no matter what else they match
+		let allowed = user_agent.is_some_and(|agent| {
+			config
+				.allowed
+				.iter()
+				.any(|allowed| contains_bytes(agent, allowed.as_bytes()))
+		});
+
+		let is_honeypot = config.rules.is_honeypot(path);
+		let signals =
+			self.shared
+				.signals(&req, &config, client_ip, allowed, custom_block, is_honeypot);
+		// clients which are blocked for making requests too quickly are offenders
+		let rate_flagged = signals.rate
+			&& self
+				.shared
+				.rate_anomaly
+				.as_ref()
+				.is_some_and(|rate| rate.score.is_none());
+		let verdict = scoring.judge(&signals).filter(|_| custom_allow);
+
+		// if it's not a bad user agent, let it continue (unless it's poking at a honeypot, which
+		// nothing legitimate would do)
+		let Some(verdict) = verdict else {
+			return match &config.escalation {
+				// agents that were let through on purpose shouldn't end up as offenders either
+				Some(escalation)
+					if is_honeypot && mode != Mode::Shadow && !allowed && custom_allow =>
+				{
+					if let Some(ip) = client_ip {
+						self.shared
+							.offenders
+							.insert(ip, self.shared.clock.since_epoch());
+					}
+					block(
+						&mut self.inner,
+						&mut self.alternate,
+						&self.shared,
+						req,
+						escalation,
+						None,
+						client_ip
 					)
-				} else {
-					self.layer.redir_url.clone()
-				};
+				}
+				_ if allowed => forward_bot(&mut self.inner, &self.shared, req, None),
+				_ if !custom_allow => forward(&mut self.inner, &self.shared, req),
+				_ => {
+					// people who were sent here by an AI chat product aren't bots, but are still
+					// worth counting
+					let referrer = req
+						.headers()
+						.get(REFERER)
+						.filter(|_| self.shared.track_ai_referrals)
+						.and_then(ai_referrer);
+					let req = if let Some(referrer) = referrer {
+						let referral_action = self
+							.shared
+							.ai_referral_action
+							.as_ref()
+							.filter(|_| mode == Mode::Enforce);
+						self.shared.report(&Detection {
+							pattern: referrer,
+							category: AgentCategory::Referral,
+							user_agent: user_agent.unwrap_or_default(),
+							path,
+							non_compliant: false,
+							client_ip,
+							escalated: false,
+							action: referral_action
+						});
+						if let Some(referral_action) = referral_action {
+							return block(
+								&mut self.inner,
+								&mut self.alternate,
+								&self.shared,
+								req,
+								referral_action,
+								Some((referrer, AgentCategory::Referral)),
+								client_ip
+							);
+						}
+						let mut req = req;
+						req.extensions_mut().insert(MatchedAgent {
+							pattern: referrer.to_owned(),
+							category: AgentCategory::Referral
+						});
+						req
+					} else {
+						req
+					};
+
+					let blocks = match mode {
+						Mode::Enforce => true,
+						Mode::RobotsTxt => is_honeypot,
+						Mode::Shadow => false
+					};
+					self.check_then_pass(req, blocks.then(|| action.clone()), client_ip)
+				}
+			};
+		};
+
+		// agents which aren't named in our robots.txt are only told to stay away from the
+		// honeypots, along with everyone else
+		let disallowed = if verdict.in_robots_txt {
+			config.rules.disallows(path)
+		} else {
+			is_honeypot
+		};
+		let non_compliant = verdict.in_robots_txt && disallowed;
+
+		// an agent which says it respects robots.txt but was caught not doing so is lying about
+		// something, so we stop giving it the benefit of the doubt
+		let escalated = config.escalation.is_some()
+			&& (is_honeypot
+				|| (rate_flagged && !verdict.challenge)
+				|| (non_compliant && verdict.category.respects_robots_txt()));
+		if let (true, Some(ip)) = (escalated, client_ip) {
+			self.shared
+				.offenders
+				.insert(ip, self.shared.clock.since_epoch());
+		}
+
+		let action = match (mode, &config.escalation) {
+			(Mode::Shadow, _) => None,
+			(Mode::RobotsTxt, _) if !disallowed => None,
+			(_, Some(escalation)) if escalated => Some(escalation),
+			_ if self.shared.placeholder_image.is_some()
+				&& IMAGE_AGENTS.contains(&verdict.pattern) =>
+				Some(&Action::Image),
+			#[cfg(feature = "challenge")]
+			_ if verdict.challenge || verdict.suspected => Some(&Action::Challenge),
+			_ => Some(action)
+		};
 
-				ServiceFut::Redirect(redir_url)
+		// clients which have passed the challenge get through it until their cookie expires
+		#[cfg(feature = "challenge")]
+		if action == Some(&Action::Challenge)
+			&& (challenge_exempt
+				|| self.shared.challenge.passed(
+					req.headers(),
+					client_ip,
+					self.shared.clock.since_epoch()
+				)) {
+			return forward(&mut self.inner, &self.shared, req);
+		}
+
+		let Verdict {
+			pattern, category, ..
+		} = verdict;
+		let detection = Detection {
+			pattern,
+			category,
+			user_agent: user_agent.unwrap_or_default(),
+			path,
+			non_compliant,
+			client_ip,
+			escalated,
+			action
+		};
+
+		self.shared.report(&detection);
+
+		match action {
+			Some(action) => block(
+				&mut self.inner,
+				&mut self.alternate,
+				&self.shared,
+				req,
+				action,
+				Some((pattern, category)),
+				client_ip
+			),
+			None => forward_bot(
+				&mut self.inner,
+				&self.shared,
+				req,
+				Some((pattern, category))
+			)
+		}
+	}
+}
+
+impl<S, A> NoAiService<S, A> {
+	/// Passes a request which nothing else detected on to the inner service, but only once the
+	/// condition from [`NoAiLayer::block_if_async`] (if there is one) has decided not to block it.
+	/// If it does block it, it's answered with `action` (or just reported if that's `None`).
+	fn check_then_pass<ReqBody, RespBody, AF>(
+		&mut self,
+		req: Request<ReqBody>,
+		action: Option<Action>,
+		client_ip: Option<IpAddr>
+	) -> ServiceFut<RespBody, S::Error, S::Future, AF>
+	where
+		S: Service<Request<ReqBody>, Response = Response<RespBody>>,
+		RespBody: From<Bytes>
+	{
+		let Some(condition) = &self.shared.block_if_async else {
+			return forward(&mut self.inner, &self.shared, req);
+		};
+
+		// the request has already been passed on by the time the check decides, so it's too late
+		// to mark it to be watermarked or send it somewhere else
+		let action =
+			action.filter(|action| !matches!(action, Action::Watermark | Action::Alternate));
+		let (req, check) = without_body(req, |req| condition.check(req));
+		let blocked = PendingBlock {
+			shared: Arc::clone(&self.shared),
+			action,
+			grpc: is_grpc(&req),
+			uri: req.uri().clone(),
+			user_agent: req.headers().get(USER_AGENT).cloned(),
+			client_ip
+		};
+
+		// most services (including axum's routers) don't start doing anything for a request
+		// until their future is polled, which won't happen unless the check lets it through
+		ServiceFut::Checking {
+			check,
+			blocked: Some(Box::new(blocked)),
+			future: Some(self.inner.call(req)),
+			in_flight: self
+				.shared
+				.load_shedding
+				.as_ref()
+				.and_then(LoadShedding::start)
+		}
+	}
+}
+
+impl NoAiShared {
+	/// Returns the [`Scoring`] that decides what to do with each request, which is the one that
+	/// the layer was given (if it was), or one that acts on each signal the way it's set up to
+	fn scoring(&self) -> Cow<'_, Scoring> {
+		if let Some(scoring) = &self.scoring {
+			return Cow::Borrowed(scoring);
+		}
+
+		#[cfg(feature = "maxmind")]
+		let network = self.geo_block.as_ref().and_then(|geo| geo.score);
+		#[cfg(not(feature = "maxmind"))]
+		let network = None;
+		#[cfg(feature = "challenge")]
+		let challenge_suspicious = self.challenge_suspicious;
+		#[cfg(not(feature = "challenge"))]
+		let challenge_suspicious = false;
+
+		Cow::Owned(Scoring::unweighted(
+			self.header_heuristics.as_ref(),
+			[
+				(Signal::Network, network),
+				(
+					Signal::Rate,
+					self.rate_anomaly.as_ref().and_then(|rate| rate.score)
+				),
+				(
+					Signal::CdnScore,
+					self.cdn_verdict.as_ref().and_then(|cdn| cdn.score)
+				)
+			],
+			challenge_suspicious
+		))
+	}
+
+	/// Collects everything about `req` that makes it look like it came from a bot. Agents which
+	/// were explicitly `allowed` only ever show that they requested a honeypot path.
+	fn signals<'a, B>(
+		&'a self,
+		req: &Request<B>,
+		config: &'a LiveConfig,
+		client_ip: Option<IpAddr>,
+		allowed: bool,
+		custom_block: bool,
+		is_honeypot: bool
+	) -> Signals<'a> {
+		let user_agent = req.headers().get(USER_AGENT).map(HeaderValue::as_bytes);
+
+		// check it against all of the bad user agents we have stored (along with whether the
+		// agent that matched was told to stay away in our robots.txt)
+		let agent = user_agent.filter(|_| !allowed).and_then(|agent| {
+			config
+				.matcher
+				.find(agent)
+				.map(|hdr| (hdr, AgentCategory::of(hdr), true))
+				.or_else(|| {
+					HEADLESS_AGENTS
+						.iter()
+						.filter(|_| self.block_headless_browsers)
+						.find(|hdr| contains_bytes(agent, hdr.as_bytes()))
+						.map(|hdr| (*hdr, AgentCategory::Scraper, false))
+				})
+		});
+
+		// bots which a CDN has checked really are what they say they are, so they shouldn't be
+		// flagged for anything but that
+		let cdn = self.cdn_verdict.as_ref().filter(|_| !allowed);
+		let verified = cdn.is_some_and(|cdn| cdn.verified(req.headers()));
+		let suspect = !allowed && !verified;
+
+		// some crawlers say who they are in other headers instead of (or as well as) their user
+		// agent
+		let header = self
+			.header_rules
+			.iter()
+			.filter(|_| !allowed)
+			.find(|(name, pattern)| {
+				req.headers()
+					.get_all(name)
+					.iter()
+					.any(|value| contains_bytes(value.as_bytes(), pattern.as_bytes()))
 			})
-			// if it's not a bad user agent, let it continue
-			.unwrap_or_else(move || ServiceFut::Inner(self.inner.call(req)))
+			.map(|(_, pattern)| pattern.as_str());
+
+		#[cfg(feature = "maxmind")]
+		let network = match (&self.geo_block, client_ip) {
+			(Some(geo), Some(ip)) if suspect => geo.check(ip),
+			_ => None
+		};
+		#[cfg(not(feature = "maxmind"))]
+		let network = None;
+
+		Signals {
+			agent,
+			header,
+			fingerprint: self
+				.fingerprints
+				.as_ref()
+				.filter(|_| suspect)
+				.and_then(|fingerprints| fingerprints.matching(req)),
+			heuristics: self
+				.header_heuristics
+				.as_ref()
+				.filter(|_| suspect)
+				.map_or(0, |heuristics| heuristics.score(req.headers())),
+			network,
+			honeypot: is_honeypot && !allowed,
+			rate: match (&self.rate_anomaly, client_ip) {
+				(Some(rate), Some(ip)) if suspect =>
+					rate.check(ip, user_agent, self.clock.since_epoch()),
+				_ => false
+			},
+			cdn: cdn.is_some_and(|cdn| !verified && cdn.flags(req.headers())),
+			condition: custom_block && !allowed
+		}
+	}
+
+	/// Records `detection` in the stats and detection sinks and passes it to the detection hook, if
+	/// they're set (and in OpenTelemetry, with the `otel` feature)
+	fn report(&self, detection: &Detection<'_>) {
+		let now = self.clock.since_epoch();
+		if let Some(stats) = &self.stats {
+			stats.record(detection, now);
+		}
+		if let Some(on_detection) = &self.on_detection {
+			on_detection(detection);
+		}
+		for sink in &self.detection_sinks {
+			sink.record(detection, now);
+		}
+		#[cfg(feature = "otel")]
+		otel::record(detection);
+	}
+
+	/// Builds the response to a request for `uri` with `action`. `matched` is the pattern and
+	/// category that the request was detected with, if it's being blocked because of a detection.
+	#[cfg_attr(not(feature = "challenge"), allow(unused_variables))]
+	fn blocked_response<RespBody: From<Bytes>>(
+		&self,
+		grpc: bool,
+		uri: &Uri,
+		user_agent: Option<&HeaderValue>,
+		client_ip: Option<IpAddr>,
+		action: &Action,
+		matched: Option<(&str, AgentCategory)>
+	) -> Response<RespBody> {
+		// grpc clients don't know what to do with anything but a grpc response, so they always
+		// get the same one
+		let mut response = if self.grpc_aware && grpc {
+			grpc_permission_denied()
+		} else {
+			action
+				.respond(self.block_message.as_ref())
+				.unwrap_or_else(|| match action {
+					Action::RedirectLoop => self.redirect_loop(uri),
+					Action::Image => self.placeholder_image(),
+					#[cfg(feature = "challenge")]
+					Action::Challenge => self
+						.challenge
+						.respond(user_agent, client_ip, self.clock.since_epoch()),
+					_ => self.redirect()
+				})
+		};
+		for (name, value) in &self.response_headers {
+			response.headers_mut().append(name, value.clone());
+		}
+
+		if let Some((pattern, category)) = matched {
+			if self.matched_header {
+				if let Ok(pattern) = HeaderValue::from_str(pattern) {
+					response
+						.headers_mut()
+						.insert(HeaderName::from_static("x-noai-matched"), pattern);
+				}
+			}
+			response.extensions_mut().insert(MatchedAgent {
+				pattern: pattern.to_owned(),
+				category
+			});
+		}
+
+		response
+	}
+
+	fn redirect<RespBody: From<Bytes>>(&self) -> Response<RespBody> {
+		// the presets (like `NoAiLayer::forbid`) don't have anywhere to redirect to, so they give
+		// the status that they answer with instead
+		let Some(redirect) = &self.redirect else {
+			let status = match handle::current(&self.config).action {
+				Action::Status(status) => status,
+				_ => StatusCode::FORBIDDEN
+			};
+			return Action::Status(status)
+				.respond(self.block_message.as_ref())
+				.expect("`Action::Status` always responds");
+		};
+
+		// if it IS one of the bad user agents, then redirect it to our url and add the extra
+		// query on the end to force refetching if we want that
+		let location = if self.force_refetching {
+			self.with_refetch_value(|value| redirect.with_param(value))
+		} else {
+			redirect.url().clone()
+		};
+		redirect_response(StatusCode::MOVED_PERMANENTLY, location)
+	}
+
+	fn placeholder_image<RespBody: From<Bytes>>(&self) -> Response<RespBody> {
+		let Some((content_type, image)) = &self.placeholder_image else {
+			return self.redirect();
+		};
+		Response::builder()
+			.header(CONTENT_TYPE, content_type)
+			.header(CACHE_CONTROL, "no-store")
+			.body(RespBody::from(image.clone()))
+			.unwrap()
+	}
+
+	fn redirect_loop<RespBody: From<Bytes>>(&self, uri: &Uri) -> Response<RespBody> {
+		let location = self
+			.with_refetch_value(|value| redirect::self_redirect(uri, &self.refetch_param, value));
+		// this sends the client back to the url it asked for, so it can't be remembered, or people
+		// who are sent the cached copy would be stuck in the loop too
+		redirect_response(StatusCode::TEMPORARY_REDIRECT, location)
+	}
+
+	/// Calls `f` with a value for the cache-busting query parameter that hasn't been used before
+	fn with_refetch_value<T>(&self, f: impl FnOnce(&str) -> T) -> T {
+		let mut value = itoa::Buffer::new();
+		f(match self.refetch_query {
+			RefetchQuery::Counter => value.format(next_refetch_counter()),
+			RefetchQuery::Timestamp => value.format(self.clock.since_epoch().as_nanos())
+		})
+	}
+}
+
+/// A redirect to `location`, which only agents are sent, so caches are told not to store it or
+/// give it to anyone else
+fn redirect_response<RespBody: From<Bytes>>(
+	status: StatusCode,
+	location: Bytes
+) -> Response<RespBody> {
+	let location = HeaderValue::from_maybe_shared(location)
+		.expect("the redirect url should be a valid header value");
+	Response::builder()
+		.status(status)
+		.header(LOCATION, location)
+		.header(CACHE_CONTROL, "no-store")
+		.header(VARY, "user-agent")
+		.body(RespBody::from(Bytes::new()))
+		.unwrap()
+}
+
+/// Passes a request on to the inner service
+fn forward<S, ReqBody, RespBody, AF>(
+	inner: &mut S,
+	shared: &NoAiShared,
+	req: Request<ReqBody>
+) -> ServiceFut<RespBody, S::Error, S::Future, AF>
+where
+	S: Service<Request<ReqBody>, Response = Response<RespBody>>,
+	RespBody: From<Bytes>
+{
+	ServiceFut::Inner {
+		in_flight: shared.load_shedding.as_ref().and_then(LoadShedding::start),
+		future: inner.call(req)
+	}
+}
+
+/// Passes a request from a bot that isn't going to be blocked on to the inner service, unless the
+/// inner service is under too much load for it (see [`NoAiLayer::shed_bots_under_load`]).
+/// `matched` is the pattern and category that it was detected with, if it was detected.
+fn forward_bot<S, ReqBody, RespBody, AF>(
+	inner: &mut S,
+	shared: &NoAiShared,
+	req: Request<ReqBody>,
+	matched: Option<(&str, AgentCategory)>
+) -> ServiceFut<RespBody, S::Error, S::Future, AF>
+where
+	S: Service<Request<ReqBody>, Response = Response<RespBody>>,
+	RespBody: From<Bytes>
+{
+	match &shared.load_shedding {
+		Some(shedding) if shedding.under_pressure(&*shared.clock) => ServiceFut::Respond {
+			response: Some(shared.blocked_response(
+				is_grpc(&req),
+				req.uri(),
+				None,
+				None,
+				&Action::Status(StatusCode::SERVICE_UNAVAILABLE),
+				matched
+			))
+		},
+		_ => forward(inner, shared, req)
 	}
 }
 
-/// The Future type that [`NoAiService::call`] produces. This has the bounds necessary to work
-/// nicely with the [`tower_service::Service`] API requirements for the associated `Future` type.
-pub enum ServiceFut<RespBody, Err, F>
+/// Answers a request with `action`. `matched` is the pattern and category that it was detected
+/// with, if it was. The exceptions are [`Action::Watermark`], for which the request is passed on to
+/// the inner service, marked so that a [`WatermarkLayer`] can find it, and [`Action::Alternate`],
+/// for which it's passed on to the alternate service (if there is one).
+fn block<S, A, ReqBody, RespBody>(
+	inner: &mut S,
+	alternate: &mut A,
+	shared: &NoAiShared,
+	mut req: Request<ReqBody>,
+	action: &Action,
+	matched: Option<(&str, AgentCategory)>,
+	client_ip: Option<IpAddr>
+) -> ServiceFut<RespBody, S::Error, S::Future, A::Future>
 where
-	RespBody: Default,
-	F: Future<Output = Result<Response<RespBody>, Err>>
+	S: Service<Request<ReqBody>, Response = Response<RespBody>>,
+	A: MaybeAlternate<Request<ReqBody>, Response<RespBody>, S::Error>,
+	RespBody: From<Bytes>
 {
-	/// This variant is created when the [`NoAiService`] doesn't find an AI USER_AGENT header in an
-	/// incoming request, and so just forwards the request on to the next service in the stack. The
-	/// `F` type is just the future that that next service returns.
-	Inner(F),
-	/// This variant is created with the [`NoAiService`] DOES find an AI USER_AGENT header and thus
-	/// redirects the request. The wrapped [`String`] is the url that it will be redirected to.
-	Redirect(String)
+	match action {
+		Action::Watermark => {
+			#[cfg(feature = "honeytokens")]
+			let honeytoken = shared.honeytokens.as_ref().map(|honeytokens| {
+				honeytokens.url(
+					matched.map_or("", |(pattern, _)| pattern),
+					client_ip,
+					req.uri().path(),
+					shared.clock.since_epoch()
+				)
+			});
+			#[cfg(not(feature = "honeytokens"))]
+			let honeytoken = None;
+			req.extensions_mut().insert(Watermarked {
+				pattern: matched.map(|(pattern, _)| pattern.to_owned()),
+				client_ip,
+				honeytoken
+			});
+			return forward_bot(inner, shared, req, matched);
+		}
+		Action::Alternate => match alternate.call(req) {
+			Ok(future) => return ServiceFut::Alternate { future },
+			// without an alternate service, it's redirected like `Action::Redirect` says
+			Err(unsent) => req = unsent
+		},
+		_ => ()
+	}
+
+	ServiceFut::Respond {
+		response: Some(shared.blocked_response(
+			is_grpc(&req),
+			req.uri(),
+			req.headers().get(USER_AGENT),
+			client_ip,
+			action,
+			matched
+		))
+	}
 }
 
-impl<RespBody, Err, F> Future for ServiceFut<RespBody, Err, F>
+/// Calls `f` with `req` without its body, which doesn't need to be copied or allocated for since
+/// the request is just taken apart and put back together around it
+fn without_body<B, T>(req: Request<B>, f: impl FnOnce(&Request<()>) -> T) -> (Request<B>, T) {
+	let (parts, body) = req.into_parts();
+	let req = Request::from_parts(parts, ());
+	let result = f(&req);
+	(Request::from_parts(req.into_parts().0, body), result)
+}
+
+fn is_grpc<B>(req: &Request<B>) -> bool {
+	req.headers()
+		.get(CONTENT_TYPE)
+		.is_some_and(|content_type| content_type.as_bytes().starts_with(b"application/grpc"))
+}
+
+/// Returns a grpc "trailers-only" response, which has no body and puts what would be the trailers
+/// in its headers instead
+fn grpc_permission_denied<RespBody: From<Bytes>>() -> Response<RespBody> {
+	Response::builder()
+		.header(CONTENT_TYPE, "application/grpc")
+		// PERMISSION_DENIED
+		.header("grpc-status", "7")
+		.header(
+			"grpc-message",
+			"automated%20AI%20access%20is%20not%20permitted"
+		)
+		.body(RespBody::from(Bytes::new()))
+		.unwrap()
+}
+
+/// Returns a different number every time it's called (until it wraps around, which won't happen in
+/// practice), starting from a random one so that the numbers aren't reused after a restart
+fn next_refetch_counter() -> u64 {
+	static COUNTER: OnceLock<AtomicU64> = OnceLock::new();
+	COUNTER
+		.get_or_init(|| AtomicU64::new(RandomState::new().build_hasher().finish()))
+		.fetch_add(1, Ordering::Relaxed)
+}
+
+pin_project! {
+	/// The Future type that [`NoAiService::call`] produces. This has the bounds necessary to work
+	/// nicely with the [`tower_service::Service`] API requirements for the associated `Future` type.
+	#[project = ServiceFutProj]
+	// `pin_project!` doesn't allow doc comments on enum fields, so they're documented on their
+	// variants instead
+	#[allow(missing_docs)]
+	pub enum ServiceFut<RespBody, Err, F, AF = Pending<Result<Response<RespBody>, Err>>>
+	where
+		RespBody: From<Bytes>,
+		F: Future<Output = Result<Response<RespBody>, Err>>
+	{
+		/// This variant is created when the [`NoAiService`] doesn't find an AI USER_AGENT header in
+		/// an incoming request, and so just forwards the request on to the next service in the
+		/// stack. `future` is just the future that that next service returns, and `in_flight`
+		/// counts the request as being handled by it for [`NoAiLayer::shed_bots_under_load`].
+		Inner {
+			#[pin]
+			future: F,
+			in_flight: Option<InFlight>
+		},
+		/// This variant is created when the [`NoAiService`] DOES find an AI USER_AGENT header, and
+		/// so responds to the request itself with whatever [`Action`] it was configured with (e.g.
+		/// by redirecting it). `response` is `None` once the future has completed.
+		Respond {
+			response: Option<Response<RespBody>>
+		},
+		/// This variant is created when nothing else detected the request, but the
+		/// [`AsyncCondition`] given to [`NoAiLayer::block_if_async`] still needs to decide whether
+		/// to block it. `future` is what the next service returned for the request, which isn't
+		/// polled unless `check` decides to let the request through.
+		Checking {
+			check: PendingCheck,
+			blocked: Option<Box<PendingBlock>>,
+			future: Option<F>,
+			in_flight: Option<InFlight>
+		},
+		/// This variant is created when the [`NoAiService`] answers a request with
+		/// [`Action::Alternate`], and so passes it on to the alternate service given to
+		/// [`NoAiLayer::alternate`] instead. `future` is what that service returned.
+		Alternate {
+			#[pin]
+			future: AF
+		}
+	}
+}
+
+impl<RespBody, Err, F, AF> Future for ServiceFut<RespBody, Err, F, AF>
 where
-	RespBody: Default,
-	F: Future<Output = Result<Response<RespBody>, Err>>
+	RespBody: From<Bytes>,
+	F: Future<Output = Result<Response<RespBody>, Err>>,
+	AF: Future<Output = Result<Response<RespBody>, Err>>
 {
 	type Output = Result<Response<RespBody>, Err>;
-	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-		// SAFETY: This is safe because we guarantee that we don't move out of the mutable
-		// reference this produces. We just need to match on &mut values here so that we can poll
-		// the inner future.
-		match unsafe { self.get_unchecked_mut() } {
-			Self::Redirect(redir_url) => Poll::Ready(Ok(Response::builder()
-				.status(StatusCode::MOVED_PERMANENTLY)
-				.header("Location", &*redir_url)
-				.body(RespBody::default())
-				.unwrap())),
-			// SAFETY: This is safe because we matched on a reference, so it hasn't moved since we
-			// looked at it inside the `Pin` over `&mut Self` above.
-			Self::Inner(f) => unsafe { Pin::new_unchecked(f) }.poll(cx)
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		match self.as_mut().project() {
+			ServiceFutProj::Respond { response } => Poll::Ready(Ok(response
+				.take()
+				.expect("ServiceFut polled after completion"))),
+			ServiceFutProj::Inner { future, .. } => future.poll(cx),
+			ServiceFutProj::Alternate { future } => future.poll(cx),
+			ServiceFutProj::Checking {
+				check,
+				blocked,
+				future,
+				in_flight
+			} => {
+				let block = ready!(Pin::new(check).poll(cx));
+				let blocked = blocked.take().expect("ServiceFut polled after completion");
+				let future = future.take().expect("ServiceFut polled after completion");
+				let in_flight = in_flight.take();
+
+				if let Some(response) = block.then(|| blocked.finish()).flatten() {
+					self.set(Self::Respond { response: None });
+					return Poll::Ready(Ok(response));
+				}
+
+				// `future` hasn't been polled yet, so it's fine to move it into place to be pinned
+				self.set(Self::Inner { future, in_flight });
+				self.poll(cx)
+			}
 		}
 	}
 }
 
 /// The [`tower`] layer which can be added to something like an [`axum::Router`]
 ///
+/// If a request goes through the same layer (or a clone of it) more than once, e.g. because it was
+/// added to both a router and one of the routes under it, only the first (outermost) one checks it,
+/// so its User-Agent isn't scanned twice and it isn't counted twice in [`NoAiStats`]. This only
+/// covers clones which haven't been configured any further since they were cloned. Layers which
+/// were created (or reconfigured) separately can block different agents, so each of them checks
+/// every request on its own, and every one that's given the same [`NoAiStats`] counts it. To only
+/// count a request once, add clones of one layer at each level instead of building a new one for
+/// each:
+///
+/// ```rust
+/// use axum::{routing::get, Router};
+/// use tower_no_ai::{NoAiLayer, NoAiStats};
+///
+/// let layer = NoAiLayer::new("https://example.com").stats(NoAiStats::new());
+///
+/// let api: Router = Router::new()
+///     .route("/items", get(|| async { "items" }))
+///     .layer(layer.clone());
+/// let app: Router = Router::new().nest("/api", api).layer(layer);
+/// ```
+///
 /// [`tower`]: https://docs.rs/tower
 /// [`axum::Router`]: https://docs.rs/axum/latest/axum/struct.Router.html
 #[derive(Clone)]
 pub struct NoAiLayer {
-	redir_url: String,
-	force_refetching: bool
+	shared: Arc<NoAiShared>
+}
+
+/// Everything a [`NoAiLayer`] was configured with. This is shared between the layer, its clones,
+/// and every service it creates, so creating a service or handling a request never needs to copy
+/// any of it.
+#[derive(Clone)]
+struct NoAiShared {
+	// where agents are redirected to, which the presets (e.g. `NoAiLayer::forbid`) don't have
+	redirect: Option<RedirectTarget>,
+	force_refetching: bool,
+	refetch_param: String,
+	refetch_query: RefetchQuery,
+	config: SharedConfig,
+	exempt_paths: Vec<String>,
+	response_headers: HeaderMap,
+	block_message: Option<Bytes>,
+	// the content type and contents of the image for `Action::Image`
+	placeholder_image: Option<(HeaderValue, Bytes)>,
+	matched_header: bool,
+	header_rules: Vec<(HeaderName, String)>,
+	header_heuristics: Option<HeaderHeuristics>,
+	block_headless_browsers: bool,
+	fingerprints: Option<FingerprintRules>,
+	grpc_aware: bool,
+	clock: Arc<dyn Clock>,
+	stats: Option<NoAiStats>,
+	on_detection: Option<Arc<DetectionHook>>,
+	detection_sinks: Vec<Arc<dyn DetectionSink>>,
+	block_if: Option<Arc<RequestPredicate>>,
+	block_only_if: Option<Arc<RequestPredicate>>,
+	block_if_async: Option<AsyncCondition>,
+	load_shedding: Option<LoadShedding>,
+	client_ip_source: Option<ClientIpSource>,
+	offenders: OffenderList,
+	// whether a `NoAiHandle` has been made for `config`, in which case it's changed in place
+	// instead of being replaced, so that the handle keeps working
+	handle_taken: Arc<AtomicBool>,
+	#[cfg(feature = "hot-reload")]
+	agents_file: Option<AgentsFile>,
+	#[cfg(feature = "maxmind")]
+	geo_block: Option<GeoBlock>,
+	rate_anomaly: Option<RateAnomaly>,
+	cdn_verdict: Option<CdnVerdict>,
+	#[cfg(feature = "honeytokens")]
+	honeytokens: Option<Honeytokens>,
+	scoring: Option<Scoring>,
+	schedule: Option<Schedule>,
+	track_ai_referrals: bool,
+	ai_referral_action: Option<Action>,
+	#[cfg(feature = "challenge")]
+	challenge: Challenge,
+	// whether requests which are only suspected of being from bots are challenged, instead of
+	// getting the usual action
+	#[cfg(feature = "challenge")]
+	challenge_suspicious: bool
+}
+
+/// The request extension that a [`NoAiService`] marks the requests it's checked with, holding the
+/// address of the layer's shared state so that the same layer doesn't check a request twice
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Screened(usize);
+
+type DetectionHook = dyn Fn(&Detection<'_>) + Send + Sync;
+type RequestPredicate = dyn Fn(&Request<()>) -> bool + Send + Sync;
+
+/// Whether a [`NoAiLayer`] blocks the agents it detects
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum Mode {
+	/// Detected agents get the configured [`Action`]. This is the default.
+	#[default]
+	Enforce,
+	/// Detected agents are recorded (in [`NoAiStats`], [`NoAiLayer::on_detection`], etc.) as usual,
+	/// but are then passed on to the inner service as if nothing had happened. This is useful for
+	/// seeing what would be blocked before turning blocking on.
+	Shadow,
+	/// Detected agents only get the configured [`Action`] when they request a path that the
+	/// robots.txt generated by [`NoAiLayer::robots_txt`] disallows them from (see
+	/// [`NoAiLayer::disallow_paths`] and [`NoAiLayer::honeypot_paths`]), and are passed on to the
+	/// inner service everywhere else. This keeps what the layer does exactly in line with what the
+	/// robots.txt says, for sites which are only partly closed off to AI agents.
+	///
+	/// Agents which are detected by something other than a pattern that's in the robots.txt (e.g.
+	/// [`NoAiLayer::header_heuristics`]) are only blocked on the honeypot paths, since those are
+	/// the only ones the robots.txt disallows for every agent. Known offenders are still blocked
+	/// everywhere.
+	RobotsTxt
+}
+
+/// What goes in the query that a [`NoAiLayer`] adds to the end of the redirect url when
+/// [`NoAiLayer::force_refetching`] is on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum RefetchQuery {
+	/// A number which goes up by one with every redirect, starting from a random number when the
+	/// process starts. This is the default.
+	#[default]
+	Counter,
+	/// The number of nanoseconds since the unix epoch, according to the layer's [`Clock`]. With the
+	/// default clock, this needs to ask the OS for the time on every redirect, and if the system
+	/// clock is before the epoch, every redirect gets the same query.
+	Timestamp
 }
 
 impl NoAiLayer {
-	/// Create a new `Self` which will redirect to the given URL when hit
+	/// Create a new `Self` which will redirect to the given URL when hit. The URL isn't checked
+	/// here, so use [`Self::builder`] instead if it comes from somewhere you don't control.
 	pub fn new(redir_url: impl Into<String>) -> Self {
+		Self::with_redirect(Some(RedirectTarget::new(
+			redir_url.into(),
+			DEFAULT_REFETCH_PARAM
+		)))
+	}
+
+	/// Create a new `Self` which redirects to `redirect`, if there is one
+	pub(crate) fn with_redirect(redirect: Option<RedirectTarget>) -> Self {
+		let shared = NoAiShared {
+			redirect,
+			force_refetching: true,
+			refetch_param: String::from(DEFAULT_REFETCH_PARAM),
+			refetch_query: RefetchQuery::Counter,
+			config: Arc::new(RwLock::new(Arc::new(LiveConfig::new()))),
+			exempt_paths: Vec::new(),
+			response_headers: HeaderMap::new(),
+			block_message: None,
+			placeholder_image: None,
+			matched_header: false,
+			header_rules: Vec::new(),
+			header_heuristics: None,
+			block_headless_browsers: false,
+			fingerprints: None,
+			grpc_aware: false,
+			clock: Arc::new(SystemClock),
+			stats: None,
+			on_detection: None,
+			detection_sinks: Vec::new(),
+			block_if: None,
+			block_only_if: None,
+			block_if_async: None,
+			load_shedding: None,
+			client_ip_source: None,
+			offenders: OffenderList::default(),
+			handle_taken: Arc::default(),
+			#[cfg(feature = "hot-reload")]
+			agents_file: None,
+			#[cfg(feature = "maxmind")]
+			geo_block: None,
+			rate_anomaly: None,
+			cdn_verdict: None,
+			#[cfg(feature = "honeytokens")]
+			honeytokens: None,
+			scoring: None,
+			schedule: None,
+			track_ai_referrals: false,
+			ai_referral_action: None,
+			#[cfg(feature = "challenge")]
+			challenge: Challenge::new(),
+			#[cfg(feature = "challenge")]
+			challenge_suspicious: false
+		};
 		Self {
-			redir_url: redir_url.into(),
-			force_refetching: true
+			shared: Arc::new(shared)
 		}
 	}
 
-	/// Force any bots which are caught to re-fetch what ever address you give them by adding a new
-	/// query (which query will change per-request) to the end of it.
+	/// Create a new `Self` which answers AI agents with a `403 Forbidden`, and tells caches not to
+	/// store that answer (so that it's never served to anyone else).
+	///
+	/// There's no URL for these presets to redirect to, so anything that would otherwise redirect
+	/// (e.g. [`Action::Image`] without [`Self::placeholder_image`], or an [`Action::Redirect`]
+	/// escalation) gets this status instead, or that of whichever [`Action::Status`] the layer is
+	/// given later on.
+	pub fn forbid() -> Self {
+		Self::rejecting(StatusCode::FORBIDDEN)
+	}
+
+	/// Create a new `Self` which answers AI agents with a `410 Gone`, and tells caches not to store
+	/// that answer (so that it's never served to anyone else). Like with [`Self::forbid`], anything
+	/// that would redirect gets this status instead.
+	pub fn gone() -> Self {
+		Self::rejecting(StatusCode::GONE)
+	}
+
+	/// Create a new `Self` which answers AI agents with a `418 I'm a teapot`, and tells caches not
+	/// to store that answer (so that it's never served to anyone else). Like with
+	/// [`Self::forbid`], anything that would redirect gets this status instead.
+	pub fn teapot() -> Self {
+		Self::rejecting(StatusCode::IM_A_TEAPOT)
+	}
+
+	/// Create a new `Self` which answers AI agents with a `451 Unavailable For Legal Reasons`, and
+	/// tells caches not to store that answer (so that it's never served to anyone else). Like with
+	/// [`Self::forbid`], anything that would redirect gets this status instead.
+	pub fn unavailable_for_legal_reasons() -> Self {
+		Self::rejecting(StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS)
+	}
+
+	fn rejecting(status: StatusCode) -> Self {
+		Self::with_redirect(None)
+			.action(Action::Status(status))
+			.response_headers(HeaderMap::from_iter([(
+				CACHE_CONTROL,
+				HeaderValue::from_static("no-store")
+			)]))
+	}
+
+	/// Create a [`NoAiLayerBuilder`], which validates the configuration when it builds the layer
+	pub fn builder() -> NoAiLayerBuilder {
+		NoAiLayerBuilder::new()
+	}
+
+	/// Force any bots which are caught to re-fetch what ever address you give them by adding a
+	/// query parameter (whose value will change per-request) to it. This is added after any query
+	/// the URL already has and before its fragment, e.g. `https://example.com/?a=b#c` becomes
+	/// `https://example.com/?a=b&nocache=<value>#c`.
 	///
 	/// If `force_refetching` is true, it will force the bot to re-fetch. This is the default. If
 	/// `force_refetching` is false, it will not do so.
 	#[must_use]
 	pub fn force_refetching(mut self, force_refetching: bool) -> Self {
-		self.force_refetching = force_refetching;
+		self.shared_mut().force_refetching = force_refetching;
+		self
+	}
+
+	/// Set the name of the query parameter that [`Self::force_refetching`] adds to the redirect
+	/// url, which is percent-encoded if it needs to be. By default, this is `nocache`.
+	#[must_use]
+	pub fn refetch_param(mut self, name: impl Into<String>) -> Self {
+		let shared = self.shared_mut();
+		shared.refetch_param = name.into();
+		shared.redirect = shared
+			.redirect
+			.as_ref()
+			.map(|redirect| RedirectTarget::new(redirect.url().clone(), &shared.refetch_param));
+		self
+	}
+
+	/// Set what the query parameter that [`Self::force_refetching`] adds to the redirect url is set
+	/// to. By default, this is [`RefetchQuery::Counter`].
+	#[must_use]
+	pub fn refetch_query(mut self, refetch_query: RefetchQuery) -> Self {
+		self.shared_mut().refetch_query = refetch_query;
+		self
+	}
+
+	/// Only redirect the agents which fall into one of the given categories, instead of all the
+	/// agents in [`AI_AGENTS`]. By default, every category is blocked.
+	///
+	/// ```rust
+	/// use tower_no_ai::{AgentCategory, NoAiLayer};
+	///
+	/// // keep the training crawlers out, but let AI search engines link to us
+	/// let layer = NoAiLayer::new("https://example.com")
+	///     .block_categories([AgentCategory::Training, AgentCategory::Scraper]);
+	/// ```
+	#[must_use]
+	pub fn block_categories(self, categories: impl IntoIterator<Item = AgentCategory>) -> Self {
+		self.configure(|config| {
+			config.rules.categories = categories.into_iter().collect();
+			config.refresh_agents();
+		})
+	}
+
+	/// Also redirect agents whose User-Agent contains any of the given patterns, no matter which
+	/// categories are blocked. These are also disallowed in the robots.txt generated by
+	/// [`Self::robots_txt`].
+	#[must_use]
+	pub fn add_agents(self, agents: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.configure(|config| config.add_agents(agents.into_iter().map(Into::into)))
+	}
+
+	/// Stop redirecting the agents with the given patterns (e.g. `"Applebot"`), even if they're in
+	/// a blocked category or were added with [`Self::add_agents`]
+	#[must_use]
+	pub fn remove_agents(self, agents: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.configure(|config| config.remove_agents(agents.into_iter().map(Into::into)))
+	}
+
+	/// Always let through agents whose User-Agent contains any of the given patterns, even if it
+	/// also matches a pattern that's blocked (e.g. to block `GPTBot` while still allowing
+	/// `OAI-SearchBot`). Since these are matched anywhere in the User-Agent, allowing `Applebot`
+	/// also allows anything which calls itself e.g. `Applebot-Extended`. These patterns are also
+	/// removed from the agents that are blocked and disallowed in the robots.txt generated by
+	/// [`Self::robots_txt`].
+	///
+	/// ```rust
+	/// use tower_no_ai::NoAiLayer;
+	///
+	/// let layer =
+	///     NoAiLayer::new("https://example.com").allow_agents(["OAI-SearchBot", "Applebot"]);
+	/// ```
+	#[must_use]
+	pub fn allow_agents(self, agents: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.configure(|config| config.allow_agents(agents.into_iter().map(Into::into)))
+	}
+
+	/// Match the given patterns (whether they're from [`AI_AGENTS`], or were added with
+	/// [`Self::add_agents`] or an `AgentsFile`) against the User-Agent in the given way, instead
+	/// of the default set with [`Self::default_match_mode`]. Short patterns, which can turn up
+	/// inside unrelated product names, are best matched as whole tokens. See
+	/// [`MatchMode::matches`] for exactly what each mode accepts.
+	///
+	/// If more than one pattern matches, the one that starts first in the User-Agent is used, and
+	/// the longest of those if several start in the same place (so `Applebot-Extended` is chosen
+	/// over `Applebot`). This goes for added patterns as well as those from [`AI_AGENTS`], except
+	/// that one from [`AI_AGENTS`] is chosen over an added one which starts in the same place and
+	/// is just as long.
+	///
+	/// ```rust
+	/// # #[cfg(feature = "test-util")] {
+	/// use http::Request;
+	/// use tower_layer::Layer;
+	/// use tower_no_ai::{
+	///     test_util::{agent_request, call, passed, Passthrough},
+	///     MatchMode, MatchedAgent, NoAiLayer
+	/// };
+	///
+	/// let mut service = NoAiLayer::new("https://example.com")
+	///     .match_mode(MatchMode::Token, ["Seekr", "omgili"])
+	///     .match_mode(MatchMode::TokenPrefix, ["yandex"])
+	///     .layer(Passthrough);
+	///
+	/// let user_agent = |user_agent: &str| {
+	///     Request::get("/").header("user-agent", user_agent).body(()).unwrap()
+	/// };
+	/// let matched = |response: &http::Response<_>| {
+	///     response
+	///         .extensions()
+	///         .get::<MatchedAgent>()
+	///         .map(|matched| matched.pattern.clone())
+	/// };
+	///
+	/// let response = call(&mut service, agent_request("Seekr")).unwrap();
+	/// assert_eq!(matched(&response).as_deref(), Some("Seekr"));
+	/// let response = call(&mut service, agent_request("SeekrBrowser")).unwrap();
+	/// assert!(passed(&response));
+	///
+	/// let response = call(&mut service, user_agent("Mozilla/5.0 (yandex.com/bots)")).unwrap();
+	/// assert_eq!(matched(&response).as_deref(), Some("yandex"));
+	/// let response = call(&mut service, user_agent("Mozilla/5.0 (notyandex.com/bots)")).unwrap();
+	/// assert!(passed(&response));
+	///
+	/// let response = call(&mut service, agent_request("Applebot-Extended")).unwrap();
+	/// assert_eq!(matched(&response).as_deref(), Some("Applebot-Extended"));
+	/// # }
+	/// ```
+	#[must_use]
+	pub fn match_mode(
+		self,
+		mode: MatchMode,
+		patterns: impl IntoIterator<Item = impl Into<String>>
+	) -> Self {
+		self.configure(|config| {
+			config
+				.match_modes
+				.set(mode, patterns.into_iter().map(Into::into));
+			config.refresh_agents();
+		})
+	}
+
+	/// Match every pattern that isn't given its own mode with [`Self::match_mode`] in the given
+	/// way. By default, patterns are matched anywhere in the User-Agent, with
+	/// [`MatchMode::Substring`].
+	#[must_use]
+	pub fn default_match_mode(self, mode: MatchMode) -> Self {
+		self.configure(|config| {
+			config.match_modes.default = mode;
+			config.refresh_agents();
+		})
+	}
+
+	/// Never do anything to requests for paths starting with any of the given prefixes, even if
+	/// they come from an AI agent, and just pass them on to the inner service
+	#[must_use]
+	pub fn exempt_paths(mut self, paths: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.shared_mut().exempt_paths = paths.into_iter().map(Into::into).collect();
+		self
+	}
+
+	/// Add the given headers to every response that this layer sends to AI agents instead of
+	/// passing their requests on to the inner service
+	///
+	/// ```rust
+	/// use http::{header::CACHE_CONTROL, HeaderMap, HeaderValue};
+	/// use tower_no_ai::NoAiLayer;
+	///
+	/// let layer = NoAiLayer::new("https://example.com").response_headers(HeaderMap::from_iter([(
+	///     CACHE_CONTROL,
+	///     HeaderValue::from_static("no-store")
+	/// )]));
+	/// ```
+	#[must_use]
+	pub fn response_headers(mut self, headers: HeaderMap) -> Self {
+		self.shared_mut().response_headers = headers;
 		self
 	}
+
+	/// Explain to AI agents why they were blocked by sending them the given message (as plain
+	/// text) along with the status code of an [`Action::Status`]. By default, the body of those
+	/// responses is empty.
+	///
+	/// ```rust
+	/// use tower_no_ai::NoAiLayer;
+	///
+	/// let layer = NoAiLayer::forbid()
+	///     .block_message("Automated AI crawling is not permitted; see /ai-policy");
+	/// ```
+	#[must_use]
+	pub fn block_message(mut self, message: impl Into<String>) -> Self {
+		self.shared_mut().block_message = Some(Bytes::from(message.into()));
+		self
+	}
+
+	/// Answer requests from the image crawlers in [`IMAGE_AGENTS`] with `image` (e.g. a
+	/// placeholder, or a blurred or low resolution version of the site's images), served as
+	/// `content_type`, instead of the usual action. Everyone else still gets the real files. This
+	/// is also the image that [`Action::Image`] responds with, so that it can be used for other
+	/// agents too.
+	///
+	/// Escalated requests (see [`Self::escalate_to`]) get the escalation action instead.
+	///
+	/// ```rust
+	/// use http::HeaderValue;
+	/// use tower_no_ai::NoAiLayer;
+	///
+	/// # let placeholder = Vec::new();
+	/// // let placeholder = std::fs::read("assets/placeholder.png").unwrap();
+	/// let layer = NoAiLayer::new("https://example.com")
+	///     .placeholder_image(HeaderValue::from_static("image/png"), placeholder);
+	/// ```
+	#[must_use]
+	pub fn placeholder_image(mut self, content_type: HeaderValue, image: impl Into<Bytes>) -> Self {
+		self.shared_mut().placeholder_image = Some((content_type, image.into()));
+		self
+	}
+
+	/// Add an `X-NoAi-Matched` header to responses sent because of a request's User-Agent,
+	/// containing the pattern which matched it, to make it easier to see which rule fired from
+	/// curl or CDN logs. This is off by default, since it tells the agents how they were caught;
+	/// the pattern is always available to outer layers as a [`MatchedAgent`] response extension.
+	#[must_use]
+	pub fn matched_header(mut self, matched_header: bool) -> Self {
+		self.shared_mut().matched_header = matched_header;
+		self
+	}
+
+	/// Also block requests where any value of the header `name` contains `pattern`, for crawlers
+	/// which identify themselves in headers other than their User-Agent (such as `From`). These are
+	/// checked right after the User-Agent, and are reported with `pattern` as their
+	/// [`Detection::pattern`]. This can be called more than once to add more rules.
+	///
+	/// ```rust
+	/// use http::header::FROM;
+	/// use tower_no_ai::NoAiLayer;
+	///
+	/// let layer = NoAiLayer::new("https://example.com").match_header(FROM, "openai.com");
+	/// ```
+	#[must_use]
+	pub fn match_header(mut self, name: HeaderName, pattern: impl Into<String>) -> Self {
+		self.shared_mut().header_rules.push((name, pattern.into()));
+		self
+	}
+
+	/// Also treat requests as coming from AI agents if their headers look too unlike a real
+	/// browser's, even if their User-Agent doesn't match any blocked pattern. See
+	/// [`HeaderHeuristics`] for more.
+	#[must_use]
+	pub fn header_heuristics(mut self, heuristics: HeaderHeuristics) -> Self {
+		self.shared_mut().header_heuristics = Some(heuristics);
+		self
+	}
+
+	/// Also block headless browsers and browser automation tools, whose User-Agents contain any of
+	/// the patterns in [`HEADLESS_AGENTS`]. This is off by default.
+	#[must_use]
+	pub fn block_headless_browsers(mut self, block: bool) -> Self {
+		self.shared_mut().block_headless_browsers = block;
+		self
+	}
+
+	/// Also block requests which arrived over a TLS connection with any of the given fingerprints
+	/// (e.g. JA4 fingerprints of the HTTP clients that a scraper uses), as found in the given
+	/// `source`. These are reported with the fingerprint as their [`Detection::pattern`].
+	///
+	/// ```rust
+	/// use tower_no_ai::{FingerprintSource, NoAiLayer};
+	///
+	/// let layer = NoAiLayer::new("https://example.com").block_fingerprints(
+	///     FingerprintSource::Header("x-ja4-fingerprint".parse().unwrap()),
+	///     ["t13d1516h2_8daaf6152771_e5627efa2ab1"]
+	/// );
+	/// ```
+	#[must_use]
+	pub fn block_fingerprints(
+		mut self,
+		source: FingerprintSource,
+		fingerprints: impl IntoIterator<Item = impl Into<String>>
+	) -> Self {
+		self.shared_mut().fingerprints = Some(FingerprintRules {
+			source,
+			blocked: fingerprints.into_iter().map(Into::into).collect()
+		});
+		self
+	}
+
+	/// Answer blocked gRPC requests (those with a `Content-Type` of `application/grpc`) with a
+	/// gRPC `PERMISSION_DENIED` status instead of the configured [`Action`], since gRPC clients
+	/// can't follow redirects or make sense of other responses. This is useful when the layer is in
+	/// front of a mix of gRPC and plain HTTP services, and is off by default.
+	#[must_use]
+	pub fn grpc_aware(mut self, grpc_aware: bool) -> Self {
+		self.shared_mut().grpc_aware = grpc_aware;
+		self
+	}
+
+	/// Use the given clock to tell the time, instead of the system's clock. See [`Clock`] for more.
+	#[must_use]
+	pub fn clock(mut self, clock: impl Clock) -> Self {
+		self.shared_mut().clock = Arc::new(clock);
+		self
+	}
+
+	/// Set whether detected agents are actually blocked (the default), or just detected and
+	/// reported. See [`Mode`] for more.
+	#[must_use]
+	pub fn mode(self, mode: Mode) -> Self {
+		self.configure(|config| config.mode = mode)
+	}
+
+	/// Set the paths which the robots.txt generated by [`Self::robots_txt`] disallows the blocked
+	/// agents from accessing (by default, everything under `/`). Unless the layer's mode is
+	/// [`Mode::RobotsTxt`], agents are still redirected no matter what path they request, but
+	/// requests for these paths are reported as [non-compliant](Detection::non_compliant), since
+	/// the agent is ignoring what robots.txt told it.
+	#[must_use]
+	pub fn disallow_paths(self, paths: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.configure(|config| config.rules.disallow = paths.into_iter().map(Into::into).collect())
+	}
+
+	/// Record statistics about every detected agent into the given collector
+	#[must_use]
+	pub fn stats(mut self, stats: NoAiStats) -> Self {
+		self.shared_mut().stats = Some(stats);
+		self
+	}
+
+	/// Call the given function every time an agent is detected, before it's redirected. This is
+	/// called synchronously in the request path, so it shouldn't block.
+	///
+	/// ```rust
+	/// use tower_no_ai::NoAiLayer;
+	///
+	/// let layer = NoAiLayer::new("https://example.com").on_detection(|detection| {
+	///     if detection.non_compliant {
+	///         eprintln!("{} is ignoring robots.txt", detection.pattern);
+	///     }
+	/// });
+	/// ```
+	#[must_use]
+	pub fn on_detection(mut self, hook: impl Fn(&Detection<'_>) + Send + Sync + 'static) -> Self {
+		self.shared_mut().on_detection = Some(Arc::new(hook));
+		self
+	}
+
+	/// Also block any request for which `condition` returns true, as if it came from an AI agent,
+	/// for detection which this layer doesn't have built in (e.g. checking for a flag in the query,
+	/// or looking the client up in an internal list). These are reported with
+	/// [`Detection::BLOCK_IF_PATTERN`] as their pattern, and agents let through with
+	/// [`Self::allow_agents`] still aren't blocked. `condition` is only given the request's parts,
+	/// since its body hasn't arrived yet. This replaces any condition set previously.
+	///
+	/// ```rust
+	/// use tower_no_ai::NoAiLayer;
+	///
+	/// let layer = NoAiLayer::new("https://example.com")
+	///     .block_if(|req| req.uri().query().is_some_and(|query| query.contains("scrape=1")));
+	/// ```
+	#[must_use]
+	pub fn block_if(
+		mut self,
+		condition: impl Fn(&Request<()>) -> bool + Send + Sync + 'static
+	) -> Self {
+		self.shared_mut().block_if = Some(Arc::new(condition));
+		self
+	}
+
+	/// Only block requests (whether they were detected by this layer or by [`Self::block_if`]) for
+	/// which `condition` also returns true, and let any others through as if they hadn't been
+	/// detected at all. `condition` is only given the request's parts, since its body hasn't
+	/// arrived yet. This replaces any condition set previously.
+	///
+	/// ```rust
+	/// use tower_no_ai::NoAiLayer;
+	///
+	/// // let our own monitoring through, whatever it calls itself
+	/// let layer = NoAiLayer::new("https://example.com")
+	///     .block_only_if(|req| !req.headers().contains_key("x-internal-monitor"));
+	/// ```
+	#[must_use]
+	pub fn block_only_if(
+		mut self,
+		condition: impl Fn(&Request<()>) -> bool + Send + Sync + 'static
+	) -> Self {
+		self.shared_mut().block_only_if = Some(Arc::new(condition));
+		self
+	}
+
+	/// Also block requests for which the given [`AsyncCondition`] decides to, which is only
+	/// checked for requests that nothing else has detected (and which [`Self::allow_agents`] and
+	/// [`Self::block_only_if`] don't let through). Those requests wait for the condition to decide
+	/// before they're passed on to the inner service, for up to the condition's timeout. These are
+	/// reported with [`Detection::BLOCK_IF_ASYNC_PATTERN`] as their pattern. This replaces any
+	/// condition set previously.
+	#[must_use]
+	pub fn block_if_async(mut self, condition: AsyncCondition) -> Self {
+		self.shared_mut().block_if_async = Some(condition);
+		self
+	}
+
+	/// When the inner service is under the given [`Pressure`], answer the requests from bots
+	/// which would otherwise have been let through (those from agents allowed with
+	/// [`Self::allow_agents`], and those which were only detected because of the layer's [`Mode`])
+	/// with a `503 Service Unavailable` instead, so that they don't take capacity away from
+	/// everyone else during a crawler storm.
+	///
+	/// ```rust
+	/// use tower_no_ai::{NoAiLayer, Pressure};
+	///
+	/// let layer = NoAiLayer::new("https://example.com")
+	///     .allow_agents(["OAI-SearchBot"])
+	///     .shed_bots_under_load(Pressure::InFlight(512));
+	/// ```
+	#[must_use]
+	pub fn shed_bots_under_load(mut self, pressure: Pressure) -> Self {
+		self.shared_mut().load_shedding = Some(LoadShedding::new(pressure));
+		self
+	}
+
+	/// Also send every detection to `sink`. This can be called more than once, to send detections
+	/// to more than one place. See [`DetectionSink`] for more.
+	#[must_use]
+	pub fn detection_sink(mut self, sink: impl DetectionSink) -> Self {
+		self.shared_mut().detection_sinks.push(Arc::new(sink));
+		self
+	}
+
+	/// Write a line of JSON about every detected agent to the given log. This is the same as
+	/// passing it to [`Self::detection_sink`].
+	#[must_use]
+	pub fn decision_log(self, log: DecisionLog) -> Self {
+		self.detection_sink(log)
+	}
+
+	/// Set what to do with the requests from AI agents. By default, they're redirected
+	/// ([`Action::Redirect`]).
+	#[must_use]
+	pub fn action(self, action: Action) -> Self {
+		self.configure(|config| config.action = action)
+	}
+
+	/// Set where to find the IP address of the client that sent each request. This isn't set by
+	/// default, and is needed for any feature which keeps track of clients across requests.
+	#[must_use]
+	pub fn client_ip_source(mut self, source: ClientIpSource) -> Self {
+		self.shared_mut().client_ip_source = Some(source);
+		self
+	}
+
+	/// Add paths which only misbehaving bots should ever request. These are disallowed for every
+	/// agent in the robots.txt generated by [`Self::robots_txt`] (so they should be somewhere that
+	/// none of your pages link to visibly), and requesting one of them gets a client escalated if
+	/// [`Self::escalate_to`] is set.
+	#[must_use]
+	pub fn honeypot_paths(self, paths: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.configure(|config| {
+			config.rules.honeypots = paths.into_iter().map(Into::into).collect()
+		})
+	}
+
+	/// Answer requests with the given (presumably harsher) action instead of the usual one when
+	/// they come from an AI agent that claims to respect robots.txt but requested a path that
+	/// robots.txt disallows, or from any client that requested one of the
+	/// [honeypot paths](Self::honeypot_paths).
+	///
+	/// If a [`ClientIpSource`] is set, the offending client's address is also added to the
+	/// [`OffenderList`], and every request from that address gets this action until its entry
+	/// expires, whatever its User-Agent is.
+	///
+	/// ```rust
+	/// use tower_no_ai::{Action, ClientIpSource, NoAiLayer};
+	///
+	/// let layer = NoAiLayer::new("https://example.com")
+	///     .client_ip_source(ClientIpSource::RightmostXForwardedFor)
+	///     .honeypot_paths(["/do-not-crawl/"])
+	///     .escalate_to(Action::Garbage(1024 * 1024));
+	/// ```
+	#[must_use]
+	pub fn escalate_to(self, action: Action) -> Self {
+		self.configure(|config| config.escalation = Some(action))
+	}
+
+	/// Use the given list to keep track of escalated clients, instead of one that's private to this
+	/// layer. This can be used to inspect or edit the list while the layer is running, or to share
+	/// it between multiple layers.
+	#[must_use]
+	pub fn offenders(mut self, offenders: OffenderList) -> Self {
+		self.shared_mut().offenders = offenders;
+		self
+	}
+
+	/// Returns a [`NoAiHandle`] which can be used to change this layer's configuration while it's
+	/// running. Once a handle has been taken, the layer and every clone of it share one
+	/// configuration, so changing it with any of the layer's methods afterwards (like
+	/// [`Self::action`]) also changes it for the handle, and for every service that the layer and
+	/// its clones have created. The handle's [`NoAiHandle::offenders`] and [`NoAiHandle::stats`]
+	/// are the ones that the layer had when it was taken, though.
+	pub fn handle(&self) -> NoAiHandle {
+		self.shared.handle_taken.store(true, Ordering::Relaxed);
+		NoAiHandle {
+			config: Arc::clone(&self.shared.config),
+			offenders: self.shared.offenders.clone(),
+			stats: self.shared.stats.clone()
+		}
+	}
+
+	/// Replaces this layer's live configuration with a modified copy of it, which doesn't affect
+	/// any clones of the layer. If a [`NoAiHandle`] has been taken, the configuration is modified
+	/// in place instead, so that the handle sees the change too.
+	fn configure(mut self, f: impl FnOnce(&mut LiveConfig)) -> Self {
+		if self.shared.handle_taken.load(Ordering::Relaxed) {
+			handle::update(&self.shared.config, f);
+			return self;
+		}
+
+		let mut config = LiveConfig::clone(&handle::current(&self.shared.config));
+		f(&mut config);
+		let shared = self.shared_mut();
+		shared.config = Arc::new(RwLock::new(Arc::new(config)));
+		// no handle has been taken for the new config yet, even if one is taken for the old one
+		// later
+		shared.handle_taken = Arc::default();
+		// the file needs to keep the new config up to date too
+		#[cfg(feature = "hot-reload")]
+		if let Some(file) = &self.shared.agents_file {
+			file.attach(&self.shared.config);
+		}
+		self
+	}
+
+	/// Returns this layer's settings for modification, first copying them if they're shared with
+	/// a clone of the layer or a service it created
+	fn shared_mut(&mut self) -> &mut NoAiShared {
+		Arc::make_mut(&mut self.shared)
+	}
+
+	/// Returns a [`RobotsTxtBuilder`] configured to disallow exactly the agents that this layer
+	/// redirects, so that what your robots.txt says and what this layer does can't contradict each
+	/// other.
+	///
+	/// ```rust
+	/// use tower_no_ai::{AgentCategory, NoAiLayer, RobotsTxtLayer};
+	///
+	/// let no_ai =
+	///     NoAiLayer::new("https://example.com").block_categories([AgentCategory::Training]);
+	/// let robots = RobotsTxtLayer::with_service(no_ai.robots_txt().service());
+	/// ```
+	pub fn robots_txt(&self) -> RobotsTxtBuilder {
+		RobotsTxtBuilder::from_rules(handle::current(&self.shared.config).rules.clone())
+	}
+
+	/// Also redirect agents whose User-Agent contains any of the patterns in the given file, which
+	/// is watched so that changes to it take effect immediately. See [`AgentsFile`] for more.
+	///
+	/// The patterns are matched just like the ones added with [`Self::add_agents`], and are also
+	/// seen by [`NoAiHandle::agents`] and [`Self::robots_txt`]. A robots.txt that's already been
+	/// generated isn't updated when the file changes, though.
+	///
+	/// Requires the `hot-reload` feature.
+	#[cfg(feature = "hot-reload")]
+	#[must_use]
+	pub fn agents_file(mut self, file: AgentsFile) -> Self {
+		file.attach(&self.shared.config);
+		self.shared_mut().agents_file = Some(file);
+		self
+	}
+
+	/// Also block (or score, with [`HeaderHeuristics`]) requests by the network that they come
+	/// from, as looked up in a MaxMind database. See [`GeoBlock`] for more.
+	///
+	/// Requires the `maxmind` feature.
+	#[cfg(feature = "maxmind")]
+	#[must_use]
+	pub fn geo_block(mut self, geo: GeoBlock) -> Self {
+		self.shared_mut().geo_block = Some(geo);
+		self
+	}
+
+	/// Also block (or score, with [`HeaderHeuristics`]) clients which make requests faster than a
+	/// person could. See [`RateAnomaly`] for more.
+	#[must_use]
+	pub fn rate_anomaly(mut self, rate: RateAnomaly) -> Self {
+		self.shared_mut().rate_anomaly = Some(rate);
+		self
+	}
+
+	/// Use the bot verdicts that a CDN passes along in request headers: skip the checks on how
+	/// requests look for bots that it's verified, and block (or score, with [`HeaderHeuristics`])
+	/// requests that it thinks are bots. See [`CdnVerdict`] for more.
+	#[must_use]
+	pub fn cdn_verdict(mut self, verdict: CdnVerdict) -> Self {
+		self.shared_mut().cdn_verdict = Some(verdict);
+		self
+	}
+
+	/// Hand out a unique URL to each agent that's [watermarked](Action::Watermark), and record
+	/// whoever requests one of them later. See [`Honeytokens`] for more.
+	///
+	/// Requires the `honeytokens` feature.
+	#[cfg(feature = "honeytokens")]
+	#[must_use]
+	pub fn honeytokens(mut self, honeytokens: Honeytokens) -> Self {
+		self.shared_mut().honeytokens = Some(honeytokens);
+		self
+	}
+
+	/// Report requests from people who followed a link from an AI chat product (as found by their
+	/// `Referer` being one of [`AI_REFERRERS`]) as detections in the [`AgentCategory::Referral`]
+	/// category, so that they show up in the stats and logs, and mark them with a [`MatchedAgent`]
+	/// request extension. They're still let through, unless [`Self::ai_referral_action`] is set.
+	/// This is off by default.
+	///
+	/// ```rust
+	/// use tower_no_ai::{NoAiLayer, NoAiStats};
+	///
+	/// let stats = NoAiStats::new();
+	/// let layer = NoAiLayer::new("https://example.com")
+	///     .track_ai_referrals(true)
+	///     .stats(stats.clone());
+	///
+	/// // ...later
+	/// for (referrer, referrer_stats) in stats.referrals() {
+	///     println!("{referrer} sent {} people here", referrer_stats.detections);
+	/// }
+	/// ```
+	#[must_use]
+	pub fn track_ai_referrals(mut self, track: bool) -> Self {
+		self.shared_mut().track_ai_referrals = track;
+		self
+	}
+
+	/// Answer requests from people referred by an AI chat product with `action`, instead of letting
+	/// them through. This also turns on [`Self::track_ai_referrals`]. These are only answered with
+	/// it in [`Mode::Enforce`], since they aren't from agents that robots.txt applies to.
+	#[must_use]
+	pub fn ai_referral_action(mut self, action: Action) -> Self {
+		let shared = self.shared_mut();
+		shared.track_ai_referrals = true;
+		shared.ai_referral_action = Some(action);
+		self
+	}
+
+	/// Use a different action or mode than usual at certain times. See [`Schedule`] for more.
+	#[must_use]
+	pub fn schedule(mut self, schedule: Schedule) -> Self {
+		self.shared_mut().schedule = Some(schedule);
+		self
+	}
+
+	/// Decide what to do with each request by adding up a weighted score for everything about it
+	/// that looks like a bot, instead of letting each of those block it on its own. See
+	/// [`Scoring`] for more.
+	#[must_use]
+	pub fn scoring(mut self, scoring: Scoring) -> Self {
+		self.shared_mut().scoring = Some(scoring);
+		self
+	}
+
+	/// Answer requests which are only suspected of coming from bots (because of their
+	/// [fingerprint](Self::block_fingerprints), [headers](Self::header_heuristics),
+	/// [request rate](Self::rate_anomaly), [CDN's verdict](Self::cdn_verdict), or a
+	/// [condition](Self::block_if), rather than because of what they say they are) with the given
+	/// [`Challenge`], instead of the usual action, so that people who happen to look like bots can
+	/// still get through. This is also the challenge that [`Action::Challenge`] uses, which has a
+	/// random key otherwise.
+	///
+	/// Requests which are escalated (see [`Self::escalate_to`]) get the escalation action instead.
+	///
+	/// Requires the `challenge` feature.
+	#[cfg(feature = "challenge")]
+	#[must_use]
+	pub fn challenge(mut self, challenge: Challenge) -> Self {
+		let shared = self.shared_mut();
+		shared.challenge = challenge;
+		shared.challenge_suspicious = true;
+		self
+	}
+
+	/// Give the layer a second service for [`Action::Alternate`] to send detected requests to,
+	/// instead of the inner service, e.g. one which serves a summary-only version of each page. The
+	/// returned layer has every other setting of this one.
+	///
+	/// ```rust
+	/// use std::convert::Infallible;
+	///
+	/// use bytes::Bytes;
+	/// use http::{Request, Response};
+	/// use tower_no_ai::{Action, NoAiLayer};
+	/// use tower_service::Service;
+	///
+	/// #[derive(Clone)]
+	/// struct SummaryOnly;
+	///
+	/// impl<B> Service<Request<B>> for SummaryOnly {
+	///     type Response = Response<Bytes>;
+	///     type Error = Infallible;
+	///     type Future = std::future::Ready<Result<Self::Response, Infallible>>;
+	///
+	///     fn poll_ready(
+	///         &mut self,
+	///         _: &mut std::task::Context<'_>
+	///     ) -> std::task::Poll<Result<(), Infallible>> {
+	///         std::task::Poll::Ready(Ok(()))
+	///     }
+	///
+	///     fn call(&mut self, _: Request<B>) -> Self::Future {
+	///         std::future::ready(Ok(Response::new(Bytes::from_static(b"A summary of the page"))))
+	///     }
+	/// }
+	///
+	/// let layer = NoAiLayer::new("https://example.com")
+	///     .action(Action::Alternate)
+	///     .alternate(SummaryOnly);
+	/// ```
+	pub fn alternate<A: Clone>(self, service: A) -> AlternateLayer<A> {
+		AlternateLayer {
+			layer: self,
+			alternate: service
+		}
+	}
+
+	/// Use this layer with services that use the types from version 0.2 of the `http` crate (e.g.
+	/// ones built on hyper 0.14), instead of version 1. See [`Http02Layer`] for what that does and
+	/// doesn't cover.
+	///
+	/// Requires the `http02` feature.
+	#[cfg(feature = "http02")]
+	#[must_use]
+	pub fn http02(self) -> Http02Layer {
+		Http02Layer { layer: self }
+	}
 }
 
 impl<S> Layer<S> for NoAiLayer {
@@ -198,27 +2034,8 @@ impl<S> Layer<S> for NoAiLayer {
 	fn layer(&self, inner: S) -> Self::Service {
 		Self::Service {
 			inner,
-			layer: self.clone()
+			alternate: (),
+			shared: Arc::clone(&self.shared)
 		}
 	}
 }
-
-/// Returns the contents of a basic robots.txt file that explicitly disallows all the known AI bots
-/// from accessing anything under the root of this website. Can be added with something like:
-///
-/// ```rust
-/// use axum::routing::{get, Router};
-/// use tower_no_ai::bot_blocking_robots_txt;
-///
-/// let router = Router::new()
-///     .route("robots.txt", get(bot_blocking_robots_txt))
-/// ```
-pub fn bot_blocking_robots_txt() -> &'static str {
-	static STORAGE: OnceLock<String> = OnceLock::new();
-
-	STORAGE.get_or_init(|| {
-		AI_AGENTS.iter().fold(String::new(), |txt, agent| {
-			format!("{txt}User-Agent: {agent}\nDisallow: /\n")
-		})
-	})
-}
@@ -0,0 +1,162 @@
+use std::fmt::Write;
+
+/// A builder for [llms.txt](https://llmstxt.org) files, which give LLMs a curated, markdown
+/// overview of a site. This can be served the same way as [`bot_blocking_robots_txt`], e.g.:
+///
+/// ```rust
+/// use axum::routing::{get, Router};
+/// use tower_no_ai::{LlmsTxtBuilder, LlmsTxtSection};
+///
+/// let llms_txt = LlmsTxtBuilder::new("Example")
+///     .summary("A site with examples on it")
+///     .section(
+///         LlmsTxtSection::new("Docs")
+///             .link_with_description("Intro", "https://example.com/intro.md", "Start here")
+///             .policy("These pages may be quoted, but not used for training")
+///     )
+///     .build();
+///
+/// assert!(llms_txt.starts_with("# Example\n\n> A site with examples on it\n"));
+///
+/// let router: Router = Router::new().route("/llms.txt", get(|| async { llms_txt }));
+/// ```
+///
+/// [`bot_blocking_robots_txt`]: crate::bot_blocking_robots_txt
+#[derive(Clone)]
+pub struct LlmsTxtBuilder {
+	title: String,
+	summary: Option<String>,
+	details: Vec<String>,
+	sections: Vec<LlmsTxtSection>
+}
+
+impl LlmsTxtBuilder {
+	/// Create a new `Self` with the given title (generally the name of the site or project), which
+	/// is the only required part of an llms.txt file
+	pub fn new(title: impl Into<String>) -> Self {
+		Self {
+			title: title.into(),
+			summary: None,
+			details: Vec::new(),
+			sections: Vec::new()
+		}
+	}
+
+	/// Set the short summary which goes right below the title
+	#[must_use]
+	pub fn summary(mut self, summary: impl Into<String>) -> Self {
+		self.summary = Some(summary.into());
+		self
+	}
+
+	/// Add a paragraph of extra information, which goes between the summary and the sections
+	#[must_use]
+	pub fn details(mut self, details: impl Into<String>) -> Self {
+		self.details.push(details.into());
+		self
+	}
+
+	/// Add a section of links to the end of the file
+	#[must_use]
+	pub fn section(mut self, section: LlmsTxtSection) -> Self {
+		self.sections.push(section);
+		self
+	}
+
+	/// Generate the contents of the llms.txt file
+	pub fn build(&self) -> String {
+		let mut txt = format!("# {}\n", self.title);
+
+		if let Some(summary) = &self.summary {
+			// writing to a string can't fail, so we ignore the results of all these `write!`s
+			_ = write!(txt, "\n> {summary}\n");
+		}
+
+		for details in &self.details {
+			_ = write!(txt, "\n{details}\n");
+		}
+
+		for section in &self.sections {
+			_ = write!(txt, "\n## {}\n\n", section.name);
+
+			if let Some(policy) = &section.policy {
+				_ = write!(txt, "{policy}\n\n");
+			}
+
+			for link in &section.links {
+				_ = write!(txt, "- [{}]({})", link.title, link.url);
+				if let Some(description) = &link.description {
+					_ = write!(txt, ": {description}");
+				}
+				txt.push('\n');
+			}
+		}
+
+		txt
+	}
+}
+
+/// A section of an llms.txt file, made up of a list of links and optionally a policy describing
+/// how the linked content may be used. See [`LlmsTxtBuilder`] for an example.
+///
+/// As per the spec, a section named `Optional` marks links which can be skipped if a shorter
+/// context is needed.
+#[derive(Clone)]
+pub struct LlmsTxtSection {
+	name: String,
+	policy: Option<String>,
+	links: Vec<LlmsTxtLink>
+}
+
+#[derive(Clone)]
+struct LlmsTxtLink {
+	title: String,
+	url: String,
+	description: Option<String>
+}
+
+impl LlmsTxtSection {
+	/// Create a new, empty section with the given name
+	pub fn new(name: impl Into<String>) -> Self {
+		Self {
+			name: name.into(),
+			policy: None,
+			links: Vec::new()
+		}
+	}
+
+	/// Set a policy for the content in this section (e.g. "may be used for search, but not for
+	/// training"), which is written out right below the section's heading
+	#[must_use]
+	pub fn policy(mut self, policy: impl Into<String>) -> Self {
+		self.policy = Some(policy.into());
+		self
+	}
+
+	/// Add a link to this section
+	#[must_use]
+	pub fn link(mut self, title: impl Into<String>, url: impl Into<String>) -> Self {
+		self.links.push(LlmsTxtLink {
+			title: title.into(),
+			url: url.into(),
+			description: None
+		});
+		self
+	}
+
+	/// Add a link to this section, along with a short description of what it links to
+	#[must_use]
+	pub fn link_with_description(
+		mut self,
+		title: impl Into<String>,
+		url: impl Into<String>,
+		description: impl Into<String>
+	) -> Self {
+		self.links.push(LlmsTxtLink {
+			title: title.into(),
+			url: url.into(),
+			description: Some(description.into())
+		});
+		self
+	}
+}
@@ -0,0 +1,162 @@
+use std::{
+	fmt::Write as _,
+	io::{self, Write},
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		mpsc::{self, Receiver, SyncSender, TrySendError},
+		Arc
+	},
+	thread,
+	time::Duration
+};
+
+use crate::{Action, Detection, DetectionSink};
+
+/// A log of every detection that a [`NoAiLayer`] makes, as one line of JSON per detection, for
+/// shipping off to a SIEM or analyzing crawler behavior later on. This is a [`DetectionSink`], so
+/// add it to a layer with [`NoAiLayer::detection_sink`] (or [`NoAiLayer::decision_log`]).
+///
+/// Each line is an object with these fields:
+/// - `timestamp`: when the request was detected, in milliseconds since the unix epoch according
+///   to the layer's [`Clock`]
+/// - `pattern` and `category`: as in [`Detection::pattern`] and [`Detection::category`]
+/// - `user_agent`: the request's User-Agent (with anything that isn't UTF-8 replaced)
+/// - `ip`: the client's IP address, or `null` if it isn't known
+/// - `path`, `non_compliant`, and `escalated`: as in [`Detection`]
+/// - `action`: what the request was answered with (`redirect`, `redirect_loop`, `status`,
+///   `garbage`, `tarpit`, `challenge`, `watermark`, `alternate`, or `image`), or `null` if it
+///   was passed on to the inner service as it was
+/// - `status`: the status code, if `action` is `status`
+///
+/// Lines are handed off without ever waiting, so if whatever's reading them falls too far behind,
+/// new lines are dropped (and counted in [`Self::dropped`]) instead of slowing down requests.
+///
+/// ```rust
+/// use tower_no_ai::{DecisionLog, NoAiLayer};
+///
+/// let (log, lines) = DecisionLog::channel(1024);
+/// let layer = NoAiLayer::new("https://example.com").decision_log(log);
+///
+/// std::thread::spawn(move || {
+///     for line in lines {
+///         println!("{line}");
+///     }
+/// });
+/// ```
+///
+/// [`NoAiLayer`]: crate::NoAiLayer
+/// [`NoAiLayer::detection_sink`]: crate::NoAiLayer::detection_sink
+/// [`NoAiLayer::decision_log`]: crate::NoAiLayer::decision_log
+/// [`Clock`]: crate::Clock
+#[derive(Clone)]
+pub struct DecisionLog {
+	sender: SyncSender<String>,
+	dropped: Arc<AtomicU64>
+}
+
+impl DecisionLog {
+	/// Create a new `Self` which sends each line (without a trailing newline) to the returned
+	/// receiver, with room for `capacity` lines to be waiting to be received
+	pub fn channel(capacity: usize) -> (Self, Receiver<String>) {
+		let (sender, receiver) = mpsc::sync_channel(capacity);
+		let log = Self {
+			sender,
+			dropped: Arc::default()
+		};
+		(log, receiver)
+	}
+
+	/// Create a new `Self` which writes every line to `writer` (e.g. a file opened for appending)
+	/// from a background thread. This needs to be able to spawn a thread, so it can't be used on
+	/// `wasm32-unknown-unknown`.
+	pub fn to_writer(mut writer: impl Write + Send + 'static) -> Self {
+		let (log, lines) = Self::channel(4096);
+		thread::spawn(move || {
+			// write out everything that's waiting before flushing, so that a burst of detections
+			// doesn't mean a burst of tiny writes. if a write fails, there's nowhere to report it
+			// to, and the next one might work out better.
+			while let Ok(line) = lines.recv() {
+				_ = writeln!(writer, "{line}");
+				while let Ok(line) = lines.try_recv() {
+					_ = writeln!(writer, "{line}");
+				}
+				_ = writer.flush();
+			}
+		});
+		log
+	}
+
+	/// Create a new `Self` which writes every line to stderr from a background thread, as with
+	/// [`Self::to_writer`]
+	pub fn stderr() -> Self {
+		Self::to_writer(io::stderr())
+	}
+
+	/// The number of lines which were dropped because whatever's reading them had fallen behind
+	pub fn dropped(&self) -> u64 {
+		self.dropped.load(Ordering::Relaxed)
+	}
+}
+
+impl DetectionSink for DecisionLog {
+	fn record(&self, detection: &Detection<'_>, since_epoch: Duration) {
+		match self.sender.try_send(json_line(detection, since_epoch)) {
+			Ok(()) | Err(TrySendError::Disconnected(_)) => (),
+			Err(TrySendError::Full(_)) => {
+				self.dropped.fetch_add(1, Ordering::Relaxed);
+			}
+		}
+	}
+}
+
+fn json_line(detection: &Detection<'_>, since_epoch: Duration) -> String {
+	let mut line = String::with_capacity(256);
+	_ = write!(line, "{{\"timestamp\":{}", since_epoch.as_millis());
+
+	line.push_str(",\"pattern\":");
+	push_json_str(&mut line, detection.pattern);
+	_ = write!(line, ",\"category\":\"{}\"", detection.category.name());
+	line.push_str(",\"user_agent\":");
+	push_json_str(&mut line, &String::from_utf8_lossy(detection.user_agent));
+
+	match detection.client_ip {
+		Some(ip) => _ = write!(line, ",\"ip\":\"{ip}\""),
+		None => line.push_str(",\"ip\":null")
+	}
+
+	line.push_str(",\"path\":");
+	push_json_str(&mut line, detection.path);
+	_ = write!(
+		line,
+		",\"non_compliant\":{},\"escalated\":{}",
+		detection.non_compliant, detection.escalated
+	);
+
+	match detection.action {
+		None => line.push_str(",\"action\":null"),
+		Some(action) => _ = write!(line, ",\"action\":\"{}\"", action.name())
+	}
+	if let Some(Action::Status(status)) = detection.action {
+		_ = write!(line, ",\"status\":{}", status.as_u16());
+	}
+
+	line.push('}');
+	line
+}
+
+/// Appends `s` to `line` as a JSON string
+fn push_json_str(line: &mut String, s: &str) {
+	line.push('"');
+	for c in s.chars() {
+		match c {
+			'"' => line.push_str("\\\""),
+			'\\' => line.push_str("\\\\"),
+			'\n' => line.push_str("\\n"),
+			'\r' => line.push_str("\\r"),
+			'\t' => line.push_str("\\t"),
+			c if c.is_control() => _ = write!(line, "\\u{:04x}", u32::from(c)),
+			c => line.push(c)
+		}
+	}
+	line.push('"');
+}
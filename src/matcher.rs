@@ -0,0 +1,295 @@
+use std::{borrow::Cow, cmp::Reverse};
+
+use crate::agents::AGENT_LIST;
+
+// a trie of every pattern in `AI_AGENTS`, which is built while the crate is compiled so that
+// matching against the default list doesn't need anything to be set up at runtime
+const TRIE_LEN: usize = Trie::<0>::max_len(AGENT_LIST);
+static TRIE: Trie<TRIE_LEN> = Trie::new(AGENT_LIST);
+
+/// How a pattern has to appear in a User-Agent for it to match, set with
+/// [`NoAiLayer::match_mode`]. The token modes split the User-Agent on `;`, `/`, `(`, `)`, and
+/// whitespace, so e.g. `Mozilla/5.0 (compatible; GPTBot/1.0)` has the tokens `Mozilla`, `5.0`,
+/// `compatible`, `GPTBot`, and `1.0`. A pattern which itself contains any of those delimiters
+/// matches across the tokens it spans.
+///
+/// [`NoAiLayer::match_mode`]: crate::NoAiLayer::match_mode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum MatchMode {
+	/// The pattern can appear anywhere in the User-Agent, even in the middle of something else.
+	/// This is the default.
+	#[default]
+	Substring,
+	/// The pattern has to be a whole token, so `Seekr` doesn't match `SeekrBrowser`
+	Token,
+	/// The pattern has to be the start of a token, so `yandex` matches `yandex.com` but not
+	/// `notyandex`
+	TokenPrefix
+}
+
+impl MatchMode {
+	/// Returns whether `pattern` appears in `user_agent` in the way that this mode requires
+	///
+	/// ```rust
+	/// use tower_no_ai::MatchMode;
+	///
+	/// let user_agent = "Mozilla/5.0 (compatible; SeekrBrowser/1.0; +https://yandex.com)";
+	///
+	/// assert!(MatchMode::Substring.matches(user_agent, "Seekr"));
+	/// assert!(!MatchMode::Token.matches(user_agent, "Seekr"));
+	/// assert!(MatchMode::Token.matches(user_agent, "SeekrBrowser"));
+	/// // `compatible;` and the space after it are both delimiters
+	/// assert!(MatchMode::Token.matches(user_agent, "compatible; SeekrBrowser/1.0"));
+	/// assert!(!MatchMode::Token.matches(user_agent, "patible; SeekrBrowser"));
+	///
+	/// // `/` is a delimiter too, so the `yandex` in the URL starts a token
+	/// assert!(MatchMode::TokenPrefix.matches(user_agent, "yandex"));
+	/// assert!(!MatchMode::TokenPrefix.matches(user_agent, "https://yandex.com"));
+	/// assert!(MatchMode::TokenPrefix.matches("yandex.com/bots", "yandex"));
+	/// assert!(!MatchMode::TokenPrefix.matches("notyandex.com/bots", "yandex"));
+	/// ```
+	pub fn matches(self, user_agent: &str, pattern: &str) -> bool {
+		find_bytes(user_agent.as_bytes(), pattern.as_bytes(), self).is_some()
+	}
+
+	/// Returns whether this mode allows a match of `needle_len` bytes that starts at `start` in
+	/// `haystack`
+	fn accepts(self, haystack: &[u8], start: usize, needle_len: usize) -> bool {
+		let end = start + needle_len;
+		let starts_token = start == 0 || is_delimiter(haystack[start - 1]);
+		let ends_token = end == haystack.len() || is_delimiter(haystack[end]);
+		match self {
+			Self::Substring => true,
+			Self::Token => starts_token && ends_token,
+			Self::TokenPrefix => starts_token
+		}
+	}
+}
+
+fn is_delimiter(byte: u8) -> bool {
+	matches!(byte, b';' | b'/' | b'(' | b')') || byte.is_ascii_whitespace()
+}
+
+/// The [`MatchMode`] of every pattern
+#[derive(Clone, Default)]
+pub(crate) struct MatchModes {
+	pub(crate) default: MatchMode,
+	pub(crate) patterns: Vec<(String, MatchMode)>
+}
+
+impl MatchModes {
+	pub(crate) fn of(&self, pattern: &str) -> MatchMode {
+		self.patterns
+			.iter()
+			.find(|(p, _)| p == pattern)
+			.map_or(self.default, |(_, mode)| *mode)
+	}
+
+	pub(crate) fn set(&mut self, mode: MatchMode, patterns: impl IntoIterator<Item = String>) {
+		for pattern in patterns {
+			match self.patterns.iter_mut().find(|(p, _)| *p == pattern) {
+				Some((_, existing)) => *existing = mode,
+				None => self.patterns.push((pattern, mode))
+			}
+		}
+	}
+}
+
+/// Finds which of a set of patterns a User-Agent contains. The patterns from [`AI_AGENTS`] are
+/// looked up in a trie built at compile time (so choosing which of them to block just means
+/// switching them on or off), and any others are searched for one at a time.
+///
+/// [`AI_AGENTS`]: crate::AI_AGENTS
+#[derive(Clone)]
+pub(crate) struct AgentMatcher {
+	// how each pattern in `AI_AGENTS` is being matched (if it is at all), by index
+	enabled: Box<[Option<MatchMode>]>,
+	others: Vec<(String, MatchMode)>
+}
+
+impl AgentMatcher {
+	pub(crate) fn new(agents: &[Cow<'static, str>], modes: &MatchModes) -> Self {
+		let mut enabled = vec![None; AGENT_LIST.len()].into_boxed_slice();
+		let mut others = Vec::new();
+
+		for agent in agents {
+			let mode = modes.of(agent);
+			match AGENT_LIST.iter().position(|known| *known == agent) {
+				Some(idx) => enabled[idx] = Some(mode),
+				None => others.push((agent.to_string(), mode))
+			}
+		}
+
+		Self { enabled, others }
+	}
+
+	/// Returns the pattern that `agent` contains, if there is one. If it contains more than one,
+	/// the one which starts first is chosen (and the longest one if more than one starts at the
+	/// same place), since that's the most specific. Patterns from [`AI_AGENTS`] win any ties
+	/// with the others, which are otherwise chosen between in the order that they were added.
+	///
+	/// [`AI_AGENTS`]: crate::AI_AGENTS
+	pub(crate) fn find(&self, agent: &[u8]) -> Option<&str> {
+		let known = TRIE
+			.find(agent, |idx, start| {
+				self.enabled[idx]
+					.is_some_and(|mode| mode.accepts(agent, start, AGENT_LIST[idx].len()))
+			})
+			.map(|(idx, start)| (AGENT_LIST[idx], start));
+
+		self.others
+			.iter()
+			.filter_map(|(pattern, mode)| {
+				find_bytes(agent, pattern.as_bytes(), *mode).map(|start| (pattern.as_str(), start))
+			})
+			.fold(known, |best, (pattern, start)| match best {
+				// a later pattern has to be strictly more specific to replace an earlier one
+				Some((best, best_start))
+					if (best_start, Reverse(best.len())) <= (start, Reverse(pattern.len())) =>
+					Some((best, best_start)),
+				_ => Some((pattern, start))
+			})
+			.map(|(pattern, _)| pattern)
+	}
+}
+
+/// Returns where `needle` first appears in `haystack` in the way that `mode` requires, if it does
+fn find_bytes(haystack: &[u8], needle: &[u8], mode: MatchMode) -> Option<usize> {
+	if needle.is_empty() {
+		return Some(0);
+	}
+	haystack
+		.windows(needle.len())
+		.enumerate()
+		.position(|(start, window)| window == needle && mode.accepts(haystack, start, needle.len()))
+}
+
+/// Returns whether `needle` appears anywhere in `haystack`
+pub(crate) fn contains_bytes(haystack: &[u8], needle: &[u8]) -> bool {
+	needle.is_empty()
+		|| haystack
+			.windows(needle.len())
+			.any(|window| window == needle)
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+	byte: u8,
+	// these are all indexes into the trie's nodes, with 0 (the root, which can't be anyone's
+	// child or sibling) meaning that there isn't one
+	first_child: u16,
+	next_sibling: u16,
+	// the index of the pattern which ends at this node plus one, or 0 if none do
+	pattern: u16
+}
+
+const EMPTY: Node = Node {
+	byte: 0,
+	first_child: 0,
+	next_sibling: 0,
+	pattern: 0
+};
+
+/// A trie with room for `LEN` nodes, each of which has its children in a linked list
+struct Trie<const LEN: usize> {
+	nodes: [Node; LEN]
+}
+
+impl<const LEN: usize> Trie<LEN> {
+	/// Returns how many nodes a trie of `patterns` could need at most
+	const fn max_len(patterns: &[&str]) -> usize {
+		let mut len = 1;
+		let mut idx = 0;
+		while idx < patterns.len() {
+			len += patterns[idx].len();
+			idx += 1;
+		}
+		len
+	}
+
+	const fn new(patterns: &[&str]) -> Self {
+		assert!(
+			LEN <= u16::MAX as usize,
+			"too many patterns to fit in a trie"
+		);
+
+		let mut nodes = [EMPTY; LEN];
+		let mut len = 1;
+
+		let mut pattern = 0;
+		while pattern < patterns.len() {
+			let bytes = patterns[pattern].as_bytes();
+			let mut node = 0;
+
+			let mut idx = 0;
+			while idx < bytes.len() {
+				let mut child = nodes[node].first_child as usize;
+				while child != 0 && nodes[child].byte != bytes[idx] {
+					child = nodes[child].next_sibling as usize;
+				}
+
+				if child == 0 {
+					child = len;
+					len += 1;
+					nodes[child] = Node {
+						byte: bytes[idx],
+						first_child: 0,
+						next_sibling: nodes[node].first_child,
+						pattern: 0
+					};
+					nodes[node].first_child = child as u16;
+				}
+
+				node = child;
+				idx += 1;
+			}
+
+			// if a pattern is in the list twice, we only need to remember one of them
+			if nodes[node].pattern == 0 {
+				nodes[node].pattern = pattern as u16 + 1;
+			}
+			pattern += 1;
+		}
+
+		Self { nodes }
+	}
+
+	/// Returns the index of the pattern that `haystack` contains which starts first (and is the
+	/// longest, out of those that start there) and where it starts, ignoring any patterns that
+	/// `enabled` rejects when they're found starting at the given index
+	fn find(
+		&self,
+		haystack: &[u8],
+		enabled: impl Fn(usize, usize) -> bool
+	) -> Option<(usize, usize)> {
+		(0..=haystack.len()).find_map(|start| {
+			let pattern_at = |node: usize| {
+				Some(self.nodes[node].pattern)
+					.filter(|pattern| *pattern != 0)
+					.map(|pattern| usize::from(pattern) - 1)
+					.filter(|pattern| enabled(*pattern, start))
+			};
+
+			let mut node = 0;
+			let mut found = pattern_at(node);
+
+			for byte in &haystack[start..] {
+				let mut child = usize::from(self.nodes[node].first_child);
+				while child != 0 && self.nodes[child].byte != *byte {
+					child = usize::from(self.nodes[child].next_sibling);
+				}
+				if child == 0 {
+					break;
+				}
+
+				node = child;
+				found = pattern_at(node).or(found);
+			}
+
+			found.map(|pattern| (pattern, start))
+		})
+	}
+}
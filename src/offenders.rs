@@ -0,0 +1,107 @@
+use std::{
+	collections::HashMap,
+	net::IpAddr,
+	sync::{Arc, Mutex, PoisonError},
+	time::Duration
+};
+
+/// A shared list of client IP addresses that have been caught misbehaving (e.g. by ignoring
+/// robots.txt or requesting a honeypot path), and so are treated harshly on every request they make
+/// until their entry expires, no matter what User-Agent they claim to be. See
+/// [`NoAiLayer::escalate_to`] for how addresses get added to it.
+///
+/// This is a cheap handle to shared state, so it can be cloned and kept around to inspect or edit
+/// the list while the layer is running. The current time is passed in to everything that depends
+/// on it, as the time since the unix epoch (e.g. from [`Clock::since_epoch`]), so that a list
+/// which is shared between layers with different clocks is kept by each of them in their own
+/// time.
+///
+/// [`NoAiLayer::escalate_to`]: crate::NoAiLayer::escalate_to
+/// [`Clock::since_epoch`]: crate::Clock::since_epoch
+#[derive(Clone)]
+pub struct OffenderList {
+	// when each address's entry expires, since the unix epoch
+	offenders: Arc<Mutex<HashMap<IpAddr, Duration>>>,
+	ttl: Duration,
+	max_len: usize
+}
+
+impl OffenderList {
+	/// Create a new, empty `Self`, whose entries all expire `ttl` after they were added
+	pub fn new(ttl: Duration) -> Self {
+		Self {
+			offenders: Arc::default(),
+			ttl,
+			max_len: 100_000
+		}
+	}
+
+	/// Set the most addresses that this list will hold at once, to bound its memory usage (by
+	/// default, 100,000). Once it's full, new offenders won't be added until older ones expire.
+	#[must_use]
+	pub fn max_len(mut self, max_len: usize) -> Self {
+		self.max_len = max_len;
+		self
+	}
+
+	/// Returns whether the given address is in this list at `now`
+	pub fn contains(&self, ip: IpAddr, now: Duration) -> bool {
+		self.offenders
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get(&ip)
+			.is_some_and(|expires| *expires > now)
+	}
+
+	/// Add the given address to this list at `now`, or reset its expiry time if it's already in it
+	pub fn insert(&self, ip: IpAddr, now: Duration) {
+		let mut offenders = self
+			.offenders
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+
+		if offenders.len() >= self.max_len && !offenders.contains_key(&ip) {
+			offenders.retain(|_, expires| *expires > now);
+			if offenders.len() >= self.max_len {
+				return;
+			}
+		}
+
+		offenders.insert(ip, now.saturating_add(self.ttl));
+	}
+
+	/// Remove the given address from this list, returning whether it was in it
+	pub fn remove(&self, ip: IpAddr) -> bool {
+		self.offenders
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.remove(&ip)
+			.is_some()
+	}
+
+	/// Remove every address from this list
+	pub fn clear(&self) {
+		self.offenders
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.clear();
+	}
+
+	/// Returns every address that's in this list at `now`
+	pub fn offenders(&self, now: Duration) -> Vec<IpAddr> {
+		self.offenders
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.iter()
+			.filter(|(_, expires)| **expires > now)
+			.map(|(ip, _)| *ip)
+			.collect()
+	}
+}
+
+impl Default for OffenderList {
+	/// Create a new, empty `Self`, whose entries expire after a day
+	fn default() -> Self {
+		Self::new(Duration::from_secs(60 * 60 * 24))
+	}
+}
@@ -0,0 +1,42 @@
+use std::sync::OnceLock;
+
+use opentelemetry::{global, metrics::Counter, trace::get_active_span, KeyValue};
+
+use crate::Detection;
+
+/// Records `detection` on the active OpenTelemetry span (as attributes, and as a
+/// `noai.detection` event) and in the `noai.detections` counter, with the `otel` feature
+pub(crate) fn record(detection: &Detection<'_>) {
+	static DETECTIONS: OnceLock<Counter<u64>> = OnceLock::new();
+
+	let mut attributes = vec![
+		KeyValue::new("noai.pattern", detection.pattern.to_owned()),
+		KeyValue::new("noai.category", detection.category.name()),
+		KeyValue::new(
+			"noai.action",
+			detection.action.map_or("pass", |action| action.name())
+		),
+		KeyValue::new("noai.non_compliant", detection.non_compliant),
+		KeyValue::new("noai.escalated", detection.escalated),
+	];
+
+	DETECTIONS
+		.get_or_init(|| {
+			global::meter("tower-no-ai")
+				.u64_counter("noai.detections")
+				.with_description("Requests from AI agents that were detected")
+				.build()
+		})
+		.add(1, &attributes);
+
+	get_active_span(|span| {
+		span.set_attributes(attributes.iter().cloned());
+
+		// these would make the counter's cardinality unbounded, so they're only on the event
+		attributes.push(KeyValue::new("noai.path", detection.path.to_owned()));
+		if let Some(ip) = detection.client_ip {
+			attributes.push(KeyValue::new("client.address", ip.to_string()));
+		}
+		span.add_event("noai.detection", attributes);
+	});
+}
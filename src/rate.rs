@@ -0,0 +1,224 @@
+use std::{
+	collections::hash_map::RandomState,
+	fmt,
+	hash::{BuildHasher, Hash, Hasher},
+	net::IpAddr,
+	sync::{Arc, Mutex, PoisonError},
+	time::Duration
+};
+
+/// What a [`RateAnomaly`] counts requests by
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RateKey {
+	/// The client's IP address. This is the default.
+	#[default]
+	Ip,
+	/// The client's IP address along with its User-Agent, so that many people behind the same
+	/// address (e.g. a NAT) are less likely to be flagged together, at the cost of missing
+	/// crawlers which change their User-Agent on every request
+	IpAndUserAgent
+}
+
+/// A detector for crawlers which request pages far faster than a person could, no matter what
+/// their User-Agent says. Each client's requests are counted over a sliding window, and the ones
+/// which make more than a certain number within it are flagged. Add it to a layer with
+/// [`NoAiLayer::rate_anomaly`].
+///
+/// Flagged requests are reported with a [`Detection::pattern`] of [`Self::PATTERN`], and the
+/// clients which made them are added to the layer's [`OffenderList`] if it has an escalation action
+/// (see [`NoAiLayer::escalate_to`]), so that they keep getting it after they slow down. Like with
+/// a `GeoBlock`, a `RateAnomaly` can be set to only [add to](Self::score) the score of a
+/// [`HeaderHeuristics`] instead.
+///
+/// Requests are only counted if their client IP is known (see [`ClientIpSource`]). The counts are
+/// kept in a fixed number of count-min sketches, so the memory used doesn't grow with the number of
+/// clients, but with a lot of traffic, some clients' counts can come out higher than they really
+/// are. Making the sketches [wider](Self::width) makes that less likely.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use tower_no_ai::{ClientIpSource, NoAiLayer, RateAnomaly};
+///
+/// // more than 120 requests in a minute is more than any person would make
+/// let rate = RateAnomaly::new(120, Duration::from_secs(60));
+///
+/// let layer = NoAiLayer::new("https://example.com")
+///     .client_ip_source(ClientIpSource::XRealIp)
+///     .rate_anomaly(rate);
+/// ```
+///
+/// [`NoAiLayer::rate_anomaly`]: crate::NoAiLayer::rate_anomaly
+/// [`NoAiLayer::escalate_to`]: crate::NoAiLayer::escalate_to
+/// [`Detection::pattern`]: crate::Detection::pattern
+/// [`OffenderList`]: crate::OffenderList
+/// [`HeaderHeuristics`]: crate::HeaderHeuristics
+/// [`ClientIpSource`]: crate::ClientIpSource
+#[derive(Clone)]
+pub struct RateAnomaly {
+	max_requests: u32,
+	window: Duration,
+	key: RateKey,
+	pub(crate) score: Option<u32>,
+	width: usize,
+	counts: Arc<Mutex<Option<Sketches>>>,
+	hasher: RandomState
+}
+
+impl RateAnomaly {
+	/// The [`Detection::pattern`] that requests flagged by a `RateAnomaly` are reported with
+	///
+	/// [`Detection::pattern`]: crate::Detection::pattern
+	pub const PATTERN: &'static str = "request rate";
+
+	/// Create a new `Self` which flags clients that make more than `max_requests` requests within
+	/// any `window` of time
+	pub fn new(max_requests: u32, window: Duration) -> Self {
+		Self {
+			max_requests,
+			window,
+			key: RateKey::Ip,
+			score: None,
+			width: 4096,
+			counts: Arc::default(),
+			hasher: RandomState::new()
+		}
+	}
+
+	/// Set what requests are counted by
+	#[must_use]
+	pub fn key(mut self, key: RateKey) -> Self {
+		self.key = key;
+		self
+	}
+
+	/// Instead of blocking flagged requests outright, add `points` to their [`HeaderHeuristics`]
+	/// score, so that they're blocked if that's enough to reach its threshold. This has no effect
+	/// unless the layer also has [`NoAiLayer::header_heuristics`] set.
+	///
+	/// [`HeaderHeuristics`]: crate::HeaderHeuristics
+	/// [`NoAiLayer::header_heuristics`]: crate::NoAiLayer::header_heuristics
+	#[must_use]
+	pub fn score(mut self, points: u32) -> Self {
+		self.score = Some(points);
+		self
+	}
+
+	/// Set how many counters each row of the sketches has (4096 by default). The sketches take up
+	/// about 128 bytes for each one, and each client's count is overestimated by around the number
+	/// of requests made in a window divided by this at most.
+	///
+	/// # Panics
+	///
+	/// If `width` is 0
+	#[must_use]
+	pub fn width(mut self, width: usize) -> Self {
+		assert!(width > 0, "a rate anomaly sketch can't have a width of 0");
+		self.width = width;
+		// the sketches are only made once something's counted, at whatever width is set by then
+		self.counts = Arc::default();
+		self
+	}
+
+	/// Counts a request from `ip` (with the given User-Agent) made `now` after the unix epoch,
+	/// returning whether its client has made too many requests
+	pub(crate) fn check(&self, ip: IpAddr, user_agent: Option<&[u8]>, now: Duration) -> bool {
+		let mut hasher = self.hasher.build_hasher();
+		ip.hash(&mut hasher);
+		if self.key == RateKey::IpAndUserAgent {
+			user_agent.hash(&mut hasher);
+		}
+		let hash = hasher.finish();
+
+		// the window is split up into buckets, which form a ring that's cleared out one bucket at a
+		// time as the window slides forward
+		let bucket_len = (self.window.as_nanos() / BUCKETS as u128).max(1);
+		let tick = (now.as_nanos() / bucket_len) as u64;
+
+		let count = self
+			.counts
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get_or_insert_with(|| Sketches::new(self.width))
+			.add(hash, tick);
+		count > self.max_requests
+	}
+}
+
+impl fmt::Debug for RateAnomaly {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("RateAnomaly")
+			.field("max_requests", &self.max_requests)
+			.field("window", &self.window)
+			.field("key", &self.key)
+			.field("score", &self.score)
+			.field("width", &self.width)
+			.finish_non_exhaustive()
+	}
+}
+
+const BUCKETS: usize = 8;
+const DEPTH: usize = 4;
+
+/// A ring of count-min sketches, one for each bucket of the window
+struct Sketches {
+	width: usize,
+	// the tick that each bucket was last counted in, so that it can be cleared once it's old
+	ticks: [u64; BUCKETS],
+	// `BUCKETS` sketches, each `DEPTH` rows of `width` counters
+	counters: Vec<u32>
+}
+
+impl Sketches {
+	fn new(width: usize) -> Self {
+		Self {
+			width,
+			ticks: [0; BUCKETS],
+			counters: vec![0; BUCKETS * DEPTH * width]
+		}
+	}
+
+	/// Adds one to the count for `hash` at `tick`, returning its count over the whole window
+	fn add(&mut self, hash: u64, tick: u64) -> u32 {
+		let current = (tick % BUCKETS as u64) as usize;
+		if self.ticks[current] != tick {
+			self.ticks[current] = tick;
+			self.sketch_mut(current).fill(0);
+		}
+
+		// each row's counter comes from a different combination of the two halves of the hash
+		let width = self.width;
+		let (h1, h2) = (hash as u32 as usize, (hash >> 32) as usize | 1);
+		let counters: [usize; DEPTH] =
+			std::array::from_fn(|row| row * width + h1.wrapping_add(row.wrapping_mul(h2)) % width);
+
+		let sketch = self.sketch_mut(current);
+		for counter in counters {
+			sketch[counter] = sketch[counter].saturating_add(1);
+		}
+
+		// buckets from before the start of the window are left alone until they're reused
+		let live = |bucket: &usize| tick.saturating_sub(self.ticks[*bucket]) < BUCKETS as u64;
+		counters
+			.iter()
+			.map(|counter| {
+				(0..BUCKETS)
+					.filter(live)
+					.map(|bucket| self.sketch(bucket)[*counter])
+					.fold(0, u32::saturating_add)
+			})
+			.min()
+			.unwrap_or(0)
+	}
+
+	fn sketch(&self, bucket: usize) -> &[u32] {
+		let len = DEPTH * self.width;
+		&self.counters[bucket * len..(bucket + 1) * len]
+	}
+
+	fn sketch_mut(&mut self, bucket: usize) -> &mut [u32] {
+		let len = DEPTH * self.width;
+		&mut self.counters[bucket * len..(bucket + 1) * len]
+	}
+}
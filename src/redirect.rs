@@ -0,0 +1,143 @@
+use std::cell::RefCell;
+
+use bytes::{Bytes, BytesMut};
+use http::Uri;
+
+/// The name of the query parameter that [`NoAiLayer::force_refetching`] adds if it isn't changed
+/// with [`NoAiLayer::refetch_param`]
+///
+/// [`NoAiLayer::force_refetching`]: crate::NoAiLayer::force_refetching
+/// [`NoAiLayer::refetch_param`]: crate::NoAiLayer::refetch_param
+pub(crate) const DEFAULT_REFETCH_PARAM: &str = "nocache";
+
+/// The URL that a layer redirects to, split up ahead of time so that a cache-busting parameter can
+/// be put in the right place (after any query that's already there, and before any fragment)
+/// without having to parse the URL again for every redirect
+#[derive(Clone)]
+pub(crate) struct RedirectTarget {
+	url: Bytes,
+	// everything up to where the value of the parameter goes, e.g.
+	// `https://example.com/?a=b&nocache=`
+	prefix: Bytes,
+	// the fragment (including its `#`), or nothing if there isn't one
+	fragment: Bytes
+}
+
+impl RedirectTarget {
+	pub(crate) fn new(url: impl Into<Bytes>, param: &str) -> Self {
+		let url = url.into();
+
+		// a fragment starts at the first `#`, and a query at the first `?` before that (RFC 3986
+		// section 3), so this can't be confused by anything that's inside of either of them
+		let fragment_start = url.iter().position(|b| *b == b'#').unwrap_or(url.len());
+		let (before_fragment, fragment) = url.split_at(fragment_start);
+
+		let separator: &[u8] = match before_fragment.iter().position(|b| *b == b'?') {
+			None => b"?",
+			// the query is empty or already ends in a separator, so we don't need another one
+			Some(query_start)
+				if query_start + 1 == before_fragment.len() || before_fragment.ends_with(b"&") =>
+				b"",
+			Some(_) => b"&"
+		};
+
+		let mut prefix = BytesMut::with_capacity(before_fragment.len() + param.len() + 2);
+		prefix.extend_from_slice(before_fragment);
+		prefix.extend_from_slice(separator);
+		percent_encode_into(&mut prefix, param);
+		prefix.extend_from_slice(b"=");
+
+		Self {
+			fragment: Bytes::copy_from_slice(fragment),
+			prefix: prefix.freeze(),
+			url
+		}
+	}
+
+	/// The URL exactly as it was given
+	pub(crate) fn url(&self) -> &Bytes {
+		&self.url
+	}
+
+	/// Returns the URL with the cache-busting parameter set to `value`, which must not need to be
+	/// percent-encoded
+	pub(crate) fn with_param(&self, value: &str) -> Bytes {
+		let len = self.prefix.len() + value.len() + self.fragment.len();
+		with_location_buf(len, |buf| {
+			buf.extend_from_slice(&self.prefix);
+			buf.extend_from_slice(value.as_bytes());
+			buf.extend_from_slice(&self.fragment);
+		})
+	}
+}
+
+/// Returns the path and query of `uri` with the query parameter `param` set to `value`, replacing
+/// any value it already had so that it doesn't keep growing as an agent follows redirects back to
+/// the same URL
+pub(crate) fn self_redirect(uri: &Uri, param: &str, value: &str) -> Bytes {
+	let path = match uri.path() {
+		// e.g. in `OPTIONS *`, which we can't redirect back to
+		path if !path.starts_with('/') => "/",
+		path => path
+	};
+	let query = uri.query().unwrap_or_default();
+
+	let mut encoded_param = BytesMut::new();
+	percent_encode_into(&mut encoded_param, param);
+
+	let len = path.len() + query.len() + encoded_param.len() + value.len() + 3;
+	with_location_buf(len, |buf| {
+		buf.extend_from_slice(path.as_bytes());
+		buf.extend_from_slice(b"?");
+		for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+			let name = pair.split('=').next().unwrap_or_default();
+			if name.as_bytes() != encoded_param {
+				buf.extend_from_slice(pair.as_bytes());
+				buf.extend_from_slice(b"&");
+			}
+		}
+		buf.extend_from_slice(&encoded_param);
+		buf.extend_from_slice(b"=");
+		buf.extend_from_slice(value.as_bytes());
+	})
+}
+
+// how much space is set aside at once for building redirect locations, so that we don't need to
+// allocate for every single one
+const LOCATION_BUF_LEN: usize = 4096;
+
+thread_local! {
+	static LOCATION_BUF: RefCell<BytesMut> = RefCell::new(BytesMut::new());
+}
+
+/// Calls `build` with an empty buffer that has room for at least `len` bytes, and returns what it
+/// wrote. The result is carved out of a buffer which is reused between calls, so this only
+/// allocates once every few hundred calls.
+fn with_location_buf(len: usize, build: impl FnOnce(&mut BytesMut)) -> Bytes {
+	LOCATION_BUF.with(|buf| {
+		let mut buf = buf.borrow_mut();
+		if buf.capacity() < len {
+			buf.reserve(len.max(LOCATION_BUF_LEN));
+		}
+		build(&mut buf);
+		buf.split().freeze()
+	})
+}
+
+/// Appends `s` to `buf` with everything other than unreserved characters percent-encoded, so that
+/// it can't end the query parameter early or produce an invalid header value
+fn percent_encode_into(buf: &mut BytesMut, s: &str) {
+	const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+	for byte in s.bytes() {
+		if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+			buf.extend_from_slice(&[byte]);
+		} else {
+			buf.extend_from_slice(&[
+				b'%',
+				HEX[usize::from(byte >> 4)],
+				HEX[usize::from(byte & 0xf)]
+			]);
+		}
+	}
+}
@@ -0,0 +1,147 @@
+use std::{
+	fs, io,
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex, PoisonError, RwLock, Weak}
+};
+
+use notify::{recommended_watcher, Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::handle::{self, LiveConfig, SharedConfig};
+
+type WeakConfig = Weak<RwLock<Arc<LiveConfig>>>;
+
+/// A file of extra agent patterns which is watched for changes and re-read whenever it changes, so
+/// that the agents a [`NoAiLayer`] blocks can be updated without restarting. Add it to a layer
+/// with [`NoAiLayer::agents_file`].
+///
+/// The file can either contain one pattern per line (with empty lines and lines starting with `#`
+/// ignored), or be a `robots.json` file from the
+/// [ai.robots.txt](https://github.com/ai-robots-txt/ai.robots.txt) project, whose keys are used as
+/// the patterns. If the file can't be read or parsed after a change, the patterns from before the
+/// change are kept. Otherwise, the new patterns replace the old ones in every layer that the file
+/// was added to.
+///
+/// Requires the `hot-reload` feature.
+///
+/// ```rust,no_run
+/// use tower_no_ai::{AgentsFile, NoAiLayer};
+///
+/// let agents = AgentsFile::watch("/etc/no-ai/agents.txt").unwrap();
+/// let layer = NoAiLayer::new("https://example.com").agents_file(agents);
+/// ```
+///
+/// [`NoAiLayer`]: crate::NoAiLayer
+/// [`NoAiLayer::agents_file`]: crate::NoAiLayer::agents_file
+#[derive(Clone)]
+pub struct AgentsFile {
+	patterns: Arc<RwLock<Arc<[String]>>>,
+	// the configs of every layer that this was added to, which are updated along with `patterns`
+	configs: Arc<Mutex<Vec<WeakConfig>>>,
+	// this is just held so that the file keeps being watched as long as something is using it
+	_watcher: Arc<RecommendedWatcher>
+}
+
+impl AgentsFile {
+	/// Read the patterns from the file at `path`, and start watching it for changes. This fails if
+	/// the file can't be read or parsed, or if it can't be watched.
+	pub fn watch(path: impl Into<PathBuf>) -> io::Result<Self> {
+		let path = path.into();
+		let patterns = Arc::new(RwLock::new(read_patterns(&path)?));
+		let configs = Arc::<Mutex<Vec<WeakConfig>>>::default();
+
+		let watched_patterns = Arc::clone(&patterns);
+		let watched_configs = Arc::clone(&configs);
+		let watched_path = path.clone();
+		let mut watcher = recommended_watcher(move |event: notify::Result<Event>| {
+			let Ok(event) = event else { return };
+			if !event
+				.paths
+				.iter()
+				.any(|p| p.file_name() == watched_path.file_name())
+			{
+				return;
+			}
+
+			if let Ok(new_patterns) = read_patterns(&watched_path) {
+				// this is held the whole time so that a layer that's being attached at the same
+				// time can't end up with the old patterns
+				let mut configs = watched_configs
+					.lock()
+					.unwrap_or_else(PoisonError::into_inner);
+				*watched_patterns
+					.write()
+					.unwrap_or_else(PoisonError::into_inner) = Arc::clone(&new_patterns);
+
+				configs.retain(|config| {
+					let Some(config) = config.upgrade() else {
+						return false;
+					};
+					set_patterns(&config, &new_patterns);
+					true
+				});
+			}
+		})
+		.map_err(notify_to_io)?;
+
+		// editors often save files by replacing them, which a watch on the file itself wouldn't
+		// survive, so we watch the directory it's in instead
+		let dir = path
+			.parent()
+			.filter(|dir| !dir.as_os_str().is_empty())
+			.unwrap_or(Path::new("."));
+		watcher
+			.watch(dir, RecursiveMode::NonRecursive)
+			.map_err(notify_to_io)?;
+
+		Ok(Self {
+			patterns,
+			configs,
+			_watcher: Arc::new(watcher)
+		})
+	}
+
+	/// Returns the patterns that were in the file when it was last read
+	pub fn patterns(&self) -> Arc<[String]> {
+		Arc::clone(&self.patterns.read().unwrap_or_else(PoisonError::into_inner))
+	}
+
+	/// Adds the file's patterns to `config`, and keeps them up to date whenever the file changes
+	pub(crate) fn attach(&self, config: &SharedConfig) {
+		let mut configs = self.configs.lock().unwrap_or_else(PoisonError::into_inner);
+		set_patterns(config, &self.patterns());
+		configs.retain(|config| config.strong_count() != 0);
+		configs.push(Arc::downgrade(config));
+	}
+}
+
+fn set_patterns(config: &SharedConfig, patterns: &[String]) {
+	handle::update(config, |config| {
+		config.rules.from_file = patterns.to_vec();
+		config.refresh_agents();
+	});
+}
+
+fn read_patterns(path: &Path) -> io::Result<Arc<[String]>> {
+	let contents = fs::read_to_string(path)?;
+
+	if contents.trim_start().starts_with('{') {
+		let robots_json: serde_json::Map<String, serde_json::Value> =
+			serde_json::from_str(&contents)
+				.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+		return Ok(robots_json.into_iter().map(|(agent, _)| agent).collect());
+	}
+
+	Ok(contents
+		.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty() && !line.starts_with('#'))
+		.map(String::from)
+		.collect())
+}
+
+fn notify_to_io(e: notify::Error) -> io::Error {
+	match e.kind {
+		notify::ErrorKind::Io(e) => e,
+		_ => io::Error::new(io::ErrorKind::Other, e)
+	}
+}
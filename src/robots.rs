@@ -0,0 +1,464 @@
+use std::{
+	borrow::Cow,
+	convert::Infallible,
+	future::{ready, Future, Ready},
+	pin::Pin,
+	sync::{Arc, OnceLock},
+	task::{Context, Poll},
+	time::{Duration, SystemTime, UNIX_EPOCH}
+};
+
+use bytes::Bytes;
+use http::{
+	header::{ALLOW, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+	HeaderMap, HeaderValue, Method, Request, Response, StatusCode
+};
+use http_body_util::Full;
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{time, AgentCategory, Clock, ContentPolicy, AI_AGENTS};
+
+/// Returns the contents of a basic robots.txt file that explicitly disallows all the known AI bots
+/// from accessing anything under the root of this website. Can be added with something like:
+///
+/// ```rust
+/// use axum::routing::{get, Router};
+/// use tower_no_ai::bot_blocking_robots_txt;
+///
+/// let router: Router = Router::new()
+///     .route("/robots.txt", get(|| async { bot_blocking_robots_txt() }));
+/// ```
+pub fn bot_blocking_robots_txt() -> &'static str {
+	static STORAGE: OnceLock<String> = OnceLock::new();
+
+	STORAGE.get_or_init(|| RobotsTxtBuilder::new().build())
+}
+
+/// A builder for robots.txt files which only disallow some of the known AI agents. If you want to
+/// disallow whatever a [`NoAiLayer`] is blocking, use [`NoAiLayer::robots_txt`] to get one of
+/// these that's already configured to match it.
+///
+/// ```rust
+/// use tower_no_ai::{AgentCategory, RobotsTxtBuilder};
+///
+/// let robots_txt = RobotsTxtBuilder::new()
+///     .categories([AgentCategory::Training])
+///     .build();
+///
+/// assert!(robots_txt.contains("User-Agent: GPTBot\n"));
+/// assert!(!robots_txt.contains("User-Agent: OAI-SearchBot\n"));
+/// ```
+///
+/// [`NoAiLayer`]: crate::NoAiLayer
+/// [`NoAiLayer::robots_txt`]: crate::NoAiLayer::robots_txt
+#[derive(Clone)]
+pub struct RobotsTxtBuilder {
+	rules: RobotsRules,
+	content_policy: Option<ContentPolicy>
+}
+
+/// The rules that a generated robots.txt contains for the known AI agents. These are shared
+/// between [`RobotsTxtBuilder`] and [`NoAiLayer`] so that the layer can tell when an agent is
+/// ignoring what the robots.txt told it.
+///
+/// [`NoAiLayer`]: crate::NoAiLayer
+#[derive(Clone)]
+pub(crate) struct RobotsRules {
+	pub(crate) categories: Vec<AgentCategory>,
+	// agents to block on top of (or to stop blocking out of) the ones in `categories`
+	pub(crate) added: Vec<String>,
+	pub(crate) removed: Vec<String>,
+	// the patterns from an `AgentsFile`, which are kept apart from `added` since they're replaced
+	// whenever the file changes
+	pub(crate) from_file: Vec<String>,
+	pub(crate) disallow: Vec<String>,
+	// these are disallowed for every agent, not just the AI ones, since nothing should be
+	// requesting them
+	pub(crate) honeypots: Vec<String>
+}
+
+impl RobotsRules {
+	pub(crate) fn new() -> Self {
+		Self {
+			categories: AgentCategory::ALL.to_vec(),
+			added: Vec::new(),
+			removed: Vec::new(),
+			from_file: Vec::new(),
+			disallow: vec!["/".into()],
+			honeypots: Vec::new()
+		}
+	}
+
+	/// Returns every agent pattern that these rules apply to
+	pub(crate) fn agents(&self) -> Vec<Cow<'static, str>> {
+		AI_AGENTS
+			.iter()
+			.filter(|agent| self.categories.contains(&AgentCategory::of(agent)))
+			.map(|agent| Cow::Borrowed(*agent))
+			.chain(
+				self.added
+					.iter()
+					.chain(&self.from_file)
+					.map(|agent| Cow::Owned(agent.clone()))
+			)
+			.filter(|agent| !self.removed.iter().any(|removed| removed == agent))
+			.collect()
+	}
+
+	pub(crate) fn is_honeypot(&self, path: &str) -> bool {
+		starts_with_any(path, &self.honeypots)
+	}
+
+	/// Whether the robots.txt generated from these rules tells the agents it covers not to request
+	/// the given path. robots.txt itself is never disallowed, since agents need to fetch it to find
+	/// out what they're allowed to do.
+	pub(crate) fn disallows(&self, path: &str) -> bool {
+		path != "/robots.txt" && (starts_with_any(path, &self.disallow) || self.is_honeypot(path))
+	}
+}
+
+/// Whether `path` starts with any of `prefixes`, skipping empty ones, since an empty `Disallow:`
+/// allows everything (as in RFC 9309) instead of matching every path
+fn starts_with_any(path: &str, prefixes: &[String]) -> bool {
+	prefixes
+		.iter()
+		.any(|prefix| !prefix.is_empty() && path.starts_with(&**prefix))
+}
+
+impl RobotsTxtBuilder {
+	/// Create a new `Self` which disallows every agent in [`AI_AGENTS`] from everything under `/`
+	pub fn new() -> Self {
+		Self::from_rules(RobotsRules::new())
+	}
+
+	pub(crate) fn from_rules(rules: RobotsRules) -> Self {
+		Self {
+			rules,
+			content_policy: None
+		}
+	}
+
+	/// Only disallow the agents which fall into one of the given categories
+	#[must_use]
+	pub fn categories(mut self, categories: impl IntoIterator<Item = AgentCategory>) -> Self {
+		self.rules.categories = categories.into_iter().collect();
+		self
+	}
+
+	/// Only disallow the agents from paths which start with one of the given prefixes, instead of
+	/// from everything under `/`. An empty prefix disallows nothing.
+	#[must_use]
+	pub fn disallow_paths(mut self, paths: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.rules.disallow = paths.into_iter().map(Into::into).collect();
+		self
+	}
+
+	/// Add `Content-Signal` rules for every other agent, generated from the given policy. See
+	/// [`ContentPolicy`] for more.
+	#[must_use]
+	pub fn content_policy(mut self, policy: ContentPolicy) -> Self {
+		self.content_policy = Some(policy);
+		self
+	}
+
+	/// Generate the contents of the robots.txt file
+	pub fn build(&self) -> String {
+		// agents only follow the most specific group that matches them, so the honeypots need to
+		// be repeated for each agent as well as being in the group for everyone else
+		let disallow = disallow_lines(self.rules.disallow.iter().chain(&self.rules.honeypots));
+
+		let mut txt = self
+			.rules
+			.agents()
+			.into_iter()
+			.fold(String::new(), |txt, agent| {
+				format!("{txt}User-Agent: {agent}\n{disallow}")
+			});
+
+		if !self.rules.honeypots.is_empty() {
+			txt.push_str("User-Agent: *\n");
+			txt.push_str(&disallow_lines(&self.rules.honeypots));
+		}
+
+		if let Some(policy) = &self.content_policy {
+			policy.write_content_signals(&mut txt);
+		}
+
+		txt
+	}
+
+	/// Create a [`RobotsTxtService`] which serves the file this builder generates
+	pub fn service(&self) -> RobotsTxtService {
+		RobotsTxtService::with_contents(self.build())
+	}
+}
+
+impl Default for RobotsTxtBuilder {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// A [`Service`] which answers every request it receives with a robots.txt file, for people who
+/// aren't using a framework that can turn [`bot_blocking_robots_txt`] into a handler for them.
+///
+/// The responses carry a `Content-Type`, an `ETag`, and a `Last-Modified` header, and requests with
+/// matching `If-None-Match` or `If-Modified-Since` headers are answered with a `304 Not Modified`.
+/// The `Last-Modified` time is the time this service was created (according to its
+/// [clock](Self::clock)). Responses use [`Full`] as their body type, which works with both hyper
+/// and axum.
+///
+/// ```rust
+/// use axum::Router;
+/// use tower_no_ai::RobotsTxtService;
+///
+/// let router: Router = Router::new().route_service("/robots.txt", RobotsTxtService::new());
+/// ```
+#[derive(Clone)]
+pub struct RobotsTxtService {
+	doc: Arc<RobotsTxtDoc>
+}
+
+struct RobotsTxtDoc {
+	body: Bytes,
+	etag: HeaderValue,
+	modified: SystemTime,
+	last_modified: HeaderValue
+}
+
+impl RobotsTxtService {
+	/// Create a new `Self` which serves the contents of [`bot_blocking_robots_txt`]
+	pub fn new() -> Self {
+		Self::with_contents(bot_blocking_robots_txt())
+	}
+
+	/// Create a new `Self` which serves the given contents instead of the default robots.txt,
+	/// e.g. if you want to add your own rules on top of the ones this crate generates.
+	pub fn with_contents(contents: impl Into<String>) -> Self {
+		Self::build(Bytes::from(contents.into()), time::since_epoch())
+	}
+
+	/// Tell the time with the given clock instead of the system's, which sets the `Last-Modified`
+	/// time to its current time. See [`Clock`] for more.
+	#[must_use]
+	pub fn clock(self, clock: impl Clock) -> Self {
+		Self::build(self.doc.body.clone(), clock.since_epoch())
+	}
+
+	fn build(body: Bytes, since_epoch: Duration) -> Self {
+		// http dates only have a resolution of seconds, so we truncate here to make sure that a
+		// client sending back the exact date we gave it is seen as up-to-date
+		let modified = UNIX_EPOCH + Duration::from_secs(since_epoch.as_secs());
+
+		let etag = format!("\"{:016x}\"", fnv1a(&body));
+
+		Self {
+			doc: Arc::new(RobotsTxtDoc {
+				body,
+				// neither of these can contain anything but visible ascii, so they're always valid
+				etag: HeaderValue::from_str(&etag).unwrap(),
+				modified,
+				last_modified: HeaderValue::from_str(&httpdate::fmt_http_date(modified)).unwrap()
+			})
+		}
+	}
+}
+
+impl Default for RobotsTxtService {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl RobotsTxtDoc {
+	fn respond<ReqBody, RespBody>(&self, req: &Request<ReqBody>) -> Response<RespBody>
+	where
+		RespBody: From<Bytes>
+	{
+		if req.method() != Method::GET && req.method() != Method::HEAD {
+			return Response::builder()
+				.status(StatusCode::METHOD_NOT_ALLOWED)
+				.header(ALLOW, "GET, HEAD")
+				.body(RespBody::from(Bytes::new()))
+				.unwrap();
+		}
+
+		let builder = Response::builder()
+			.header(ETAG, self.etag.clone())
+			.header(LAST_MODIFIED, self.last_modified.clone());
+
+		if self.is_fresh(req.headers()) {
+			builder
+				.status(StatusCode::NOT_MODIFIED)
+				.body(RespBody::from(Bytes::new()))
+		} else {
+			builder
+				.status(StatusCode::OK)
+				.header(CONTENT_TYPE, "text/plain; charset=utf-8")
+				.body(RespBody::from(self.body.clone()))
+		}
+		.unwrap()
+	}
+
+	/// Checks whether the client sending these headers already has the current version of this
+	/// document, following the precedence rules of RFC 9110 section 13.2.2
+	fn is_fresh(&self, headers: &HeaderMap) -> bool {
+		if headers.contains_key(IF_NONE_MATCH) {
+			// If-None-Match uses the weak comparison function, so we ignore any `W/` prefixes
+			let etag = self.etag.as_bytes();
+			return headers
+				.get_all(IF_NONE_MATCH)
+				.iter()
+				.filter_map(|val| val.to_str().ok())
+				.flat_map(|val| val.split(','))
+				.map(str::trim)
+				.any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag).as_bytes() == etag);
+		}
+
+		headers
+			.get(IF_MODIFIED_SINCE)
+			.and_then(|val| val.to_str().ok())
+			.and_then(|val| httpdate::parse_http_date(val).ok())
+			.is_some_and(|since| self.modified <= since)
+	}
+}
+
+impl<ReqBody> Service<Request<ReqBody>> for RobotsTxtService {
+	type Error = Infallible;
+	type Future = Ready<Result<Self::Response, Self::Error>>;
+	type Response = Response<Full<Bytes>>;
+
+	fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+		ready(Ok(self.doc.respond(&req)))
+	}
+}
+
+/// A [`Layer`] which serves a robots.txt file (through a [`RobotsTxtService`]) at `/robots.txt`
+/// and passes every other request on to the service it wraps.
+///
+/// ```rust
+/// use axum::routing::{get, Router};
+/// use tower_no_ai::RobotsTxtLayer;
+///
+/// let router: Router = Router::new()
+///     .route("/", get(|| async { "Hello, World!" }))
+///     .layer(RobotsTxtLayer::new());
+/// ```
+#[derive(Clone, Default)]
+pub struct RobotsTxtLayer {
+	service: RobotsTxtService
+}
+
+impl RobotsTxtLayer {
+	/// Create a new `Self` which serves the contents of [`bot_blocking_robots_txt`]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Create a new `Self` which responds to requests for `/robots.txt` with the given service
+	pub fn with_service(service: RobotsTxtService) -> Self {
+		Self { service }
+	}
+}
+
+impl<S> Layer<S> for RobotsTxtLayer {
+	type Service = ServeRobotsTxt<S>;
+	fn layer(&self, inner: S) -> Self::Service {
+		Self::Service {
+			inner,
+			robots: self.service.clone()
+		}
+	}
+}
+
+/// The service produced by [`RobotsTxtLayer`]
+#[derive(Clone)]
+pub struct ServeRobotsTxt<S> {
+	inner: S,
+	robots: RobotsTxtService
+}
+
+impl<S, ReqBody, RespBody> Service<Request<ReqBody>> for ServeRobotsTxt<S>
+where
+	S: Service<Request<ReqBody>, Response = Response<RespBody>>,
+	RespBody: From<Bytes>
+{
+	type Error = S::Error;
+	type Future = RobotsTxtFut<RespBody, Self::Error, S::Future>;
+	type Response = Response<RespBody>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+		if req.uri().path() == "/robots.txt" {
+			RobotsTxtFut::RobotsTxt {
+				response: Some(self.robots.doc.respond(&req))
+			}
+		} else {
+			RobotsTxtFut::Inner {
+				future: self.inner.call(req)
+			}
+		}
+	}
+}
+
+pin_project! {
+	/// The Future type that [`ServeRobotsTxt::call`] produces
+	#[project = RobotsTxtFutProj]
+	// `pin_project!` doesn't allow doc comments on enum fields, so they're documented on their
+	// variants instead
+	#[allow(missing_docs)]
+	pub enum RobotsTxtFut<RespBody, Err, F>
+	where
+		F: Future<Output = Result<Response<RespBody>, Err>>
+	{
+		/// The request was for something other than robots.txt, so it was passed on to the inner
+		/// service, which returned `future`.
+		Inner {
+			#[pin]
+			future: F
+		},
+		/// The request was for robots.txt, and `response` is the response to it. This is `None`
+		/// once the future has completed.
+		RobotsTxt {
+			response: Option<Response<RespBody>>
+		}
+	}
+}
+
+impl<RespBody, Err, F> Future for RobotsTxtFut<RespBody, Err, F>
+where
+	F: Future<Output = Result<Response<RespBody>, Err>>
+{
+	type Output = Result<Response<RespBody>, Err>;
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		match self.project() {
+			RobotsTxtFutProj::RobotsTxt { response } => Poll::Ready(Ok(response
+				.take()
+				.expect("RobotsTxtFut polled after completion"))),
+			RobotsTxtFutProj::Inner { future } => future.poll(cx)
+		}
+	}
+}
+
+fn disallow_lines<'a>(paths: impl IntoIterator<Item = &'a String>) -> String {
+	paths.into_iter().fold(String::new(), |txt, path| {
+		format!("{txt}Disallow: {path}\n")
+	})
+}
+
+/// A simple implementation of the FNV-1a hash, which we use (instead of std's `DefaultHasher`) so
+/// that the ETags we generate for the same contents stay the same across compiler versions.
+fn fnv1a(data: &[u8]) -> u64 {
+	data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+		(hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+	})
+}
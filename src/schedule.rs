@@ -0,0 +1,218 @@
+use std::time::Duration;
+
+use crate::{Action, Mode};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// A day of the week, for [`TimeWindow::on`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[allow(missing_docs)]
+pub enum Weekday {
+	Monday,
+	Tuesday,
+	Wednesday,
+	Thursday,
+	Friday,
+	Saturday,
+	Sunday
+}
+
+impl Weekday {
+	/// Monday to Friday
+	pub const WEEKDAYS: [Self; 5] = [
+		Self::Monday,
+		Self::Tuesday,
+		Self::Wednesday,
+		Self::Thursday,
+		Self::Friday
+	];
+
+	/// Saturday and Sunday
+	pub const WEEKEND: [Self; 2] = [Self::Saturday, Self::Sunday];
+
+	/// Returns the day that `days` days after the unix epoch (a Thursday) falls on
+	fn after_epoch(days: u64) -> Self {
+		const ALL: [Weekday; 7] = [
+			Weekday::Thursday,
+			Weekday::Friday,
+			Weekday::Saturday,
+			Weekday::Sunday,
+			Weekday::Monday,
+			Weekday::Tuesday,
+			Weekday::Wednesday
+		];
+		ALL[(days % 7) as usize]
+	}
+}
+
+/// A span of time that a [`Schedule`] changes what a layer does during
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimeWindow {
+	kind: WindowKind,
+	// every day, if empty
+	days: Vec<Weekday>
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum WindowKind {
+	// seconds since midnight in the schedule's time zone. `from` is after `to` for windows which
+	// go past midnight.
+	Daily { from: u64, to: u64 },
+	// since the unix epoch
+	Between { start: Duration, end: Duration }
+}
+
+impl TimeWindow {
+	/// Create a new `Self` which lasts from the time `from` (as `(hour, minute)`, on a 24 hour
+	/// clock) until `to` every day, in the [`Schedule`]'s time zone. If `to` is earlier than
+	/// `from`, the window goes on past midnight, into the next day.
+	///
+	/// # Panics
+	///
+	/// If either of the times isn't a real time of day (e.g. `(24, 0)` or `(9, 60)`)
+	pub fn daily(from: (u8, u8), to: (u8, u8)) -> Self {
+		let seconds = |(hour, minute): (u8, u8)| {
+			assert!(
+				hour < 24 && minute < 60,
+				"{hour:02}:{minute:02} isn't a time of day"
+			);
+			u64::from(hour) * 60 * 60 + u64::from(minute) * 60
+		};
+		Self {
+			kind: WindowKind::Daily {
+				from: seconds(from),
+				to: seconds(to)
+			},
+			days: Vec::new()
+		}
+	}
+
+	/// Create a new `Self` which lasts from `start` until `end`, both since the unix epoch (as
+	/// told by the layer's [`Clock`]), e.g. for the duration of a launch
+	///
+	/// [`Clock`]: crate::Clock
+	pub fn between(start: Duration, end: Duration) -> Self {
+		Self {
+			kind: WindowKind::Between { start, end },
+			days: Vec::new()
+		}
+	}
+
+	/// Only have this window on the given days. For a [daily](Self::daily) window which goes past
+	/// midnight, this is the day that it starts on.
+	#[must_use]
+	pub fn on(mut self, days: impl IntoIterator<Item = Weekday>) -> Self {
+		self.days.extend(days);
+		self
+	}
+
+	/// Whether `now` (since the unix epoch, after being moved into the schedule's time zone) is in
+	/// this window
+	fn contains(&self, now: Duration, local: u64) -> bool {
+		let (in_window, day) = match self.kind {
+			WindowKind::Between { start, end } => (start <= now && now < end, local / DAY),
+			WindowKind::Daily { from, to } => {
+				let (day, time) = (local / DAY, local % DAY);
+				match from <= to {
+					true => (from <= time && time < to, day),
+					// the part after midnight belongs to the day before
+					false if time >= from => (true, day),
+					false => (time < to, day.wrapping_sub(1))
+				}
+			}
+		};
+		in_window && (self.days.is_empty() || self.days.contains(&Weekday::after_epoch(day)))
+	}
+}
+
+/// A schedule of times during which a layer uses a different [`Action`] or [`Mode`] than usual,
+/// e.g. to waste crawlers' time at night, answer them with a plain `403 Forbidden` during business
+/// hours, and only watch them during a launch. Add it to a layer with [`NoAiLayer::schedule`].
+///
+/// The schedule is checked against the layer's [`Clock`] on every request. When more than one
+/// window that changes the action (or the mode) is going on at once, the one that was added first
+/// is used, and outside of all of them, the layer's usual action and mode are.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use http::StatusCode;
+/// use tower_no_ai::{Action, Mode, NoAiLayer, Schedule, TimeWindow, Weekday};
+///
+/// let launch = Duration::from_secs(1_767_225_600);
+///
+/// let schedule = Schedule::new()
+///     // in UTC+1
+///     .utc_offset(60)
+///     .action(TimeWindow::daily((22, 0), (6, 0)), Action::Garbage(1024 * 1024))
+///     .action(
+///         TimeWindow::daily((9, 0), (17, 0)).on(Weekday::WEEKDAYS),
+///         Action::Status(StatusCode::FORBIDDEN)
+///     )
+///     .mode(
+///         TimeWindow::between(launch, launch + Duration::from_secs(24 * 60 * 60)),
+///         Mode::Shadow
+///     );
+///
+/// let layer = NoAiLayer::new("https://example.com").schedule(schedule);
+/// ```
+///
+/// [`NoAiLayer::schedule`]: crate::NoAiLayer::schedule
+/// [`Clock`]: crate::Clock
+#[derive(Clone, Debug, Default)]
+pub struct Schedule {
+	// in seconds
+	utc_offset: i64,
+	actions: Vec<(TimeWindow, Action)>,
+	modes: Vec<(TimeWindow, Mode)>
+}
+
+impl Schedule {
+	/// Create a new, empty `Self`, whose daily windows are in UTC
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set how many minutes ahead of UTC the time zone that daily windows are in is (e.g. `-300`
+	/// for UTC-5). This doesn't follow daylight saving time.
+	#[must_use]
+	pub fn utc_offset(mut self, minutes: i32) -> Self {
+		self.utc_offset = i64::from(minutes) * 60;
+		self
+	}
+
+	/// Answer detected requests with `action` during `window`
+	#[must_use]
+	pub fn action(mut self, window: TimeWindow, action: Action) -> Self {
+		self.actions.push((window, action));
+		self
+	}
+
+	/// Switch to `mode` during `window`
+	#[must_use]
+	pub fn mode(mut self, window: TimeWindow, mode: Mode) -> Self {
+		self.modes.push((window, mode));
+		self
+	}
+
+	/// Returns the mode and action to use at `now` (since the unix epoch), given the usual ones
+	pub(crate) fn current<'a>(
+		&'a self,
+		now: Duration,
+		mode: Mode,
+		action: &'a Action
+	) -> (Mode, &'a Action) {
+		let local = now.as_secs().saturating_add_signed(self.utc_offset);
+		let mode = self
+			.modes
+			.iter()
+			.find(|(window, _)| window.contains(now, local))
+			.map_or(mode, |(_, mode)| *mode);
+		let action = self
+			.actions
+			.iter()
+			.find(|(window, _)| window.contains(now, local))
+			.map_or(action, |(_, action)| action);
+		(mode, action)
+	}
+}
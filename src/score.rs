@@ -0,0 +1,288 @@
+use crate::{AgentCategory, CdnVerdict, Detection, HeaderHeuristics, RateAnomaly};
+
+/// Something about a request which makes it look like it came from a bot, which a [`Scoring`]
+/// gives a weight to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Signal {
+	/// Its User-Agent matched a blocked pattern (including those from `NoAiLayer::agents_file` and
+	/// [`NoAiLayer::block_headless_browsers`])
+	///
+	/// [`NoAiLayer::block_headless_browsers`]: crate::NoAiLayer::block_headless_browsers
+	UserAgent,
+	/// Another of its headers matched a rule from [`NoAiLayer::match_header`]
+	///
+	/// [`NoAiLayer::match_header`]: crate::NoAiLayer::match_header
+	Header,
+	/// Its fingerprint matched one from [`NoAiLayer::block_fingerprints`]
+	///
+	/// [`NoAiLayer::block_fingerprints`]: crate::NoAiLayer::block_fingerprints
+	Fingerprint,
+	/// Each point of its [`HeaderHeuristics`] score, so that this is weighted by how unlike a
+	/// browser's its headers are
+	HeaderHeuristics,
+	/// It came from a network in the layer's `GeoBlock`
+	Network,
+	/// It requested one of the layer's [honeypot paths](crate::NoAiLayer::honeypot_paths)
+	Honeypot,
+	/// Its client has made more requests than the layer's [`RateAnomaly`] allows
+	Rate,
+	/// The condition given to [`NoAiLayer::block_if`] flagged it
+	///
+	/// [`NoAiLayer::block_if`]: crate::NoAiLayer::block_if
+	Condition,
+	/// Its CDN's bot score was below the layer's [`CdnVerdict`] threshold
+	CdnScore
+}
+
+impl Signal {
+	/// Every signal, in the order that they're preferred in when reporting what a request was
+	/// detected by
+	const ALL: [Self; 9] = [
+		Self::UserAgent,
+		Self::Header,
+		Self::Fingerprint,
+		Self::Network,
+		Self::Rate,
+		Self::CdnScore,
+		Self::HeaderHeuristics,
+		Self::Condition,
+		Self::Honeypot
+	];
+}
+
+/// A way of deciding what to do with each request by adding up a weight for every [`Signal`]
+/// that it shows, instead of letting any one of them block it on its own. Requests whose score
+/// reaches the [block threshold](Self::block_at) get the layer's usual action, and (with the
+/// `challenge` feature) those that only reach the challenge threshold (`Self::challenge_at`) get
+/// `Action::Challenge`. Everything else is let through. Set it up with [`NoAiLayer::scoring`].
+///
+/// Requests are reported with the [`Detection::pattern`] of the User-Agent (or other header) that
+/// matched, if one did, and otherwise with that of the first signal that would've reached the
+/// block threshold on its own, or failing that, the signal that added the most to their score
+/// ([`Self::HONEYPOT_PATTERN`] for [`Signal::Honeypot`]).
+///
+/// A layer without a `Scoring` still decides this way, with one in which every signal except
+/// for [`Signal::Honeypot`] blocks on its own, and each point of a request's [`HeaderHeuristics`]
+/// score counts towards [`HeaderHeuristics::threshold`] along with any points that the layer's
+/// `GeoBlock`, [`RateAnomaly`], and [`CdnVerdict`] were told to add instead of blocking.
+///
+/// With the default weights, every signal blocks a request on its own except for
+/// [`Signal::HeaderHeuristics`], which takes 4 points (like [`HeaderHeuristics`] does by default),
+/// so the layer behaves much like it does without a `Scoring`. The signals which only say what a
+/// request looks like can then be turned down so that it takes more than one of them:
+///
+/// ```rust
+/// use tower_no_ai::{HeaderHeuristics, NoAiLayer, Scoring, Signal};
+///
+/// let scoring = Scoring::new()
+///     .weight(Signal::Network, 40)
+///     .weight(Signal::Rate, 40)
+///     .weight(Signal::HeaderHeuristics, 15);
+///
+/// let layer = NoAiLayer::new("https://example.com")
+///     .header_heuristics(HeaderHeuristics::new())
+///     .scoring(scoring);
+/// ```
+///
+/// Each signal is only checked for if the layer is set up to look for it (e.g. a layer without
+/// [`NoAiLayer::header_heuristics`] never scores [`Signal::HeaderHeuristics`]). The thresholds of
+/// the things that look for them (like [`HeaderHeuristics::threshold`]), and the scores that they
+/// add to [`HeaderHeuristics`] on their own, are ignored. With a `Scoring`, requests aren't
+/// challenged just for being suspected of coming from bots (see `NoAiLayer::challenge`) either,
+/// since this decides that instead.
+///
+/// [`NoAiLayer::scoring`]: crate::NoAiLayer::scoring
+/// [`NoAiLayer::header_heuristics`]: crate::NoAiLayer::header_heuristics
+#[derive(Clone, Debug)]
+pub struct Scoring {
+	weights: [u32; Signal::ALL.len()],
+	block_at: u32,
+	challenge_at: Option<u32>,
+	// whether requests which were only detected by how they look should be challenged, which is
+	// only ever set for the `Scoring` that a layer uses when it wasn't given one
+	challenge_suspected: bool
+}
+
+/// What a request showed, for a [`Scoring`] to score
+pub(crate) struct Signals<'a> {
+	pub(crate) agent: Option<(&'a str, AgentCategory, bool)>,
+	pub(crate) header: Option<&'a str>,
+	pub(crate) fingerprint: Option<&'a str>,
+	pub(crate) heuristics: u32,
+	pub(crate) network: Option<&'a str>,
+	pub(crate) honeypot: bool,
+	pub(crate) rate: bool,
+	pub(crate) cdn: bool,
+	pub(crate) condition: bool
+}
+
+/// What a [`Scoring`] decided about a request that it thinks came from a bot
+pub(crate) struct Verdict<'a> {
+	/// The pattern and category to report it with
+	pub(crate) pattern: &'a str,
+	pub(crate) category: AgentCategory,
+	/// Whether the agent that matched was told to stay away in our robots.txt
+	pub(crate) in_robots_txt: bool,
+	/// Whether its score only reached the challenge threshold
+	pub(crate) challenge: bool,
+	/// Whether it should be challenged for only being detected by how it looks, rather than by
+	/// saying what it is
+	#[cfg_attr(not(feature = "challenge"), allow(dead_code))]
+	pub(crate) suspected: bool
+}
+
+impl Scoring {
+	/// The [`Detection::pattern`] that requests are reported with when requesting a honeypot path
+	/// added the most to their score
+	pub const HONEYPOT_PATTERN: &'static str = "honeypot";
+
+	/// Create a new `Self` with the default weights, which blocks requests with a score of 100 or
+	/// more, and doesn't challenge any
+	pub fn new() -> Self {
+		let mut scoring = Self {
+			weights: [100; Signal::ALL.len()],
+			block_at: 100,
+			challenge_at: None,
+			challenge_suspected: false
+		};
+		scoring.weights[Signal::HeaderHeuristics as usize] = 25;
+		scoring
+	}
+
+	/// Create the `Self` that a layer uses when it isn't given one, which blocks on every signal
+	/// the layer looks for, except that `heuristics` needs its threshold to be reached, and the
+	/// `points` that a signal was set up to add to it instead (e.g. with [`RateAnomaly::score`])
+	/// count towards that
+	pub(crate) fn unweighted(
+		heuristics: Option<&HeaderHeuristics>,
+		points: impl IntoIterator<Item = (Signal, Option<u32>)>,
+		challenge_suspected: bool
+	) -> Self {
+		let block_at = heuristics.map_or(1, |heuristics| heuristics.threshold.max(1));
+		let mut scoring = Self {
+			weights: [block_at; Signal::ALL.len()],
+			block_at,
+			challenge_at: None,
+			challenge_suspected
+		};
+		scoring.weights[Signal::HeaderHeuristics as usize] = 1;
+		scoring.weights[Signal::Honeypot as usize] = 0;
+		for (signal, points) in points {
+			if let Some(points) = points {
+				// the points have nothing to add to without any heuristics
+				scoring.weights[signal as usize] = if heuristics.is_some() { points } else { 0 };
+			}
+		}
+		scoring
+	}
+
+	/// Set how much `signal` adds to a request's score. A weight of 0 ignores it.
+	#[must_use]
+	pub fn weight(mut self, signal: Signal, weight: u32) -> Self {
+		self.weights[signal as usize] = weight;
+		self
+	}
+
+	/// Set the score at which requests are given the layer's action
+	#[must_use]
+	pub fn block_at(mut self, score: u32) -> Self {
+		self.block_at = score;
+		self
+	}
+
+	/// Set the score at which requests are challenged, if they don't reach [`Self::block_at`].
+	/// Since a challenge is much less of a bother than being blocked, this can be set a lot lower.
+	///
+	/// Requires the `challenge` feature.
+	#[cfg(feature = "challenge")]
+	#[must_use]
+	pub fn challenge_at(mut self, score: u32) -> Self {
+		self.challenge_at = Some(score);
+		self
+	}
+
+	/// Returns whether this ever challenges requests
+	#[cfg_attr(not(feature = "challenge"), allow(dead_code))]
+	pub(crate) fn challenges(&self) -> bool {
+		self.challenge_at.is_some() || self.challenge_suspected
+	}
+
+	/// Scores `signals`, returning what to do about the request if it should be acted on at all
+	pub(crate) fn judge<'a>(&self, signals: &Signals<'a>) -> Option<Verdict<'a>> {
+		let scored = |signal: Signal| {
+			let shown = match signal {
+				Signal::UserAgent => u32::from(signals.agent.is_some()),
+				Signal::Header => u32::from(signals.header.is_some()),
+				Signal::Fingerprint => u32::from(signals.fingerprint.is_some()),
+				Signal::HeaderHeuristics => signals.heuristics,
+				Signal::Network => u32::from(signals.network.is_some()),
+				Signal::Honeypot => u32::from(signals.honeypot),
+				Signal::Rate => u32::from(signals.rate),
+				Signal::CdnScore => u32::from(signals.cdn),
+				Signal::Condition => u32::from(signals.condition)
+			};
+			shown.saturating_mul(self.weights[signal as usize])
+		};
+
+		let score = Signal::ALL
+			.into_iter()
+			.map(scored)
+			.fold(0, u32::saturating_add);
+		if score == 0 {
+			return None;
+		}
+		let challenge = match self.challenge_at {
+			_ if score >= self.block_at => false,
+			Some(challenge_at) if score >= challenge_at => true,
+			_ => return None
+		};
+
+		// what the request says it is explains it best, then the first signal that would've been
+		// enough on its own, and otherwise, the first of those that added the most (`max_by_key`
+		// would find the last)
+		let top = [Signal::UserAgent, Signal::Header]
+			.into_iter()
+			.find(|signal| scored(*signal) > 0)
+			.or_else(|| {
+				Signal::ALL
+					.into_iter()
+					.find(|signal| scored(*signal) >= self.block_at)
+			})
+			.or_else(|| {
+				Signal::ALL
+					.into_iter()
+					.rev()
+					.max_by_key(|signal| scored(*signal))
+			})?;
+		let scraper =
+			|pattern: Option<&'a str>| pattern.map(|p| (p, AgentCategory::Scraper, false));
+		let (pattern, category, in_robots_txt) = match top {
+			Signal::UserAgent => signals.agent,
+			Signal::Header => signals
+				.header
+				.map(|pattern| (pattern, AgentCategory::of(pattern), false)),
+			Signal::Fingerprint => scraper(signals.fingerprint),
+			Signal::HeaderHeuristics => scraper(Some(HeaderHeuristics::PATTERN)),
+			Signal::Network => scraper(signals.network),
+			Signal::Honeypot => scraper(Some(Self::HONEYPOT_PATTERN)),
+			Signal::Rate => scraper(Some(RateAnomaly::PATTERN)),
+			Signal::CdnScore => scraper(Some(CdnVerdict::PATTERN)),
+			Signal::Condition => scraper(Some(Detection::BLOCK_IF_PATTERN))
+		}?;
+		Some(Verdict {
+			pattern,
+			category,
+			in_robots_txt,
+			challenge,
+			suspected: self.challenge_suspected
+				&& !matches!(top, Signal::UserAgent | Signal::Header)
+		})
+	}
+}
+
+impl Default for Scoring {
+	fn default() -> Self {
+		Self::new()
+	}
+}
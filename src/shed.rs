@@ -0,0 +1,97 @@
+use std::{
+	sync::{
+		atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+		Arc
+	},
+	time::Duration
+};
+
+use crate::Clock;
+
+/// The signal which tells a [`NoAiLayer`] that the service behind it is under too much load, for
+/// [`NoAiLayer::shed_bots_under_load`]
+///
+/// [`NoAiLayer`]: crate::NoAiLayer
+/// [`NoAiLayer::shed_bots_under_load`]: crate::NoAiLayer::shed_bots_under_load
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum Pressure {
+	/// At least this many requests are currently being handled by the inner service (counting
+	/// every request that this layer passed on to it, not just those from bots)
+	InFlight(usize),
+	/// The inner service's `poll_ready` has returned `Poll::Pending` within the given duration,
+	/// e.g. because it's a `tower::limit::ConcurrencyLimit` which has run out of permits
+	InnerNotReady(Duration),
+	/// The given flag is set, for load signals which this crate doesn't know how to measure (such
+	/// as the CPU usage or the length of a database's queue). This can be set from anywhere.
+	Flag(Arc<AtomicBool>)
+}
+
+/// The part of a layer that keeps track of how much load the inner service is under
+#[derive(Clone)]
+pub(crate) struct LoadShedding {
+	pressure: Pressure,
+	state: Arc<LoadState>
+}
+
+#[derive(Default)]
+struct LoadState {
+	in_flight: AtomicUsize,
+	// when the inner service was last not ready, in nanoseconds since the unix epoch according to
+	// the layer's clock, or 0 if it's always been ready
+	last_not_ready: AtomicU64
+}
+
+impl LoadShedding {
+	pub(crate) fn new(pressure: Pressure) -> Self {
+		Self {
+			pressure,
+			state: Arc::default()
+		}
+	}
+
+	/// Starts counting a request that's been passed on to the inner service, if that's needed to
+	/// tell when it's under pressure. The request stops being counted when the returned guard is
+	/// dropped.
+	pub(crate) fn start(&self) -> Option<InFlight> {
+		matches!(self.pressure, Pressure::InFlight(_)).then(|| {
+			self.state.in_flight.fetch_add(1, Ordering::Relaxed);
+			InFlight(Arc::clone(&self.state))
+		})
+	}
+
+	/// Records that the inner service wasn't ready to take a request
+	pub(crate) fn not_ready(&self, clock: &dyn Clock) {
+		if matches!(self.pressure, Pressure::InnerNotReady(_)) {
+			// this can't be 0 in practice, which we rely on to mean that it's never happened
+			let now = clock.since_epoch().as_nanos() as u64;
+			self.state
+				.last_not_ready
+				.store(now.max(1), Ordering::Relaxed);
+		}
+	}
+
+	/// Whether the inner service is currently under pressure, and bot traffic should be shed
+	pub(crate) fn under_pressure(&self, clock: &dyn Clock) -> bool {
+		match &self.pressure {
+			Pressure::InFlight(max) => self.state.in_flight.load(Ordering::Relaxed) >= *max,
+			Pressure::InnerNotReady(within) => {
+				let last = self.state.last_not_ready.load(Ordering::Relaxed);
+				last != 0 && {
+					let now = clock.since_epoch().as_nanos() as u64;
+					Duration::from_nanos(now.saturating_sub(last)) <= *within
+				}
+			}
+			Pressure::Flag(flag) => flag.load(Ordering::Relaxed)
+		}
+	}
+}
+
+/// Counts a request as being handled by the inner service until it's dropped
+pub struct InFlight(Arc<LoadState>);
+
+impl Drop for InFlight {
+	fn drop(&mut self) {
+		self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+	}
+}
@@ -0,0 +1,32 @@
+//! Helpers for the features which sign things with HMAC-SHA256
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+/// Appends `bytes` to `out` as lowercase hex
+pub(crate) fn push_hex(out: &mut String, bytes: &[u8]) {
+	for byte in bytes {
+		out.push(char::from(HEX[usize::from(byte >> 4)]));
+		out.push(char::from(HEX[usize::from(byte & 0xf)]));
+	}
+}
+
+pub(crate) fn decode_hex(hex: &[u8]) -> Option<Vec<u8>> {
+	if hex.len() % 2 != 0 {
+		return None;
+	}
+	let digit = |b: u8| HEX.iter().position(|h| *h == b).map(|d| d as u8);
+	hex.chunks(2)
+		.map(|pair| Some(digit(pair[0])? << 4 | digit(pair[1])?))
+		.collect()
+}
+
+/// Returns a key from the operating system's secure random number generator
+///
+/// # Panics
+///
+/// If there isn't one, since any key we could come up with instead could be guessed
+pub(crate) fn random_key() -> [u8; 32] {
+	let mut key = [0; 32];
+	getrandom::getrandom(&mut key).expect("couldn't get any randomness from the operating system");
+	key
+}
@@ -0,0 +1,173 @@
+use std::fmt::Write;
+
+/// The set of machine-readable usage preferences that can be attached to a path with a
+/// [`ContentPolicy`]. Any preference left unset isn't emitted at all, which leaves it up to the
+/// agent to decide what to do.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ContentSignals {
+	search: Option<bool>,
+	ai_input: Option<bool>,
+	ai_train: Option<bool>,
+	tdm_reservation: Option<bool>
+}
+
+impl ContentSignals {
+	/// Create a new `Self` with no preferences set
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Whether this content may be indexed and linked to by search engines (including AI search
+	/// engines), as the `search` content signal
+	#[must_use]
+	pub fn search(mut self, allowed: bool) -> Self {
+		self.search = Some(allowed);
+		self
+	}
+
+	/// Whether this content may be fed into AI models as input when answering a query (e.g.
+	/// retrieval-augmented generation), as the `ai-input` content signal
+	#[must_use]
+	pub fn ai_input(mut self, allowed: bool) -> Self {
+		self.ai_input = Some(allowed);
+		self
+	}
+
+	/// Whether this content may be used to train AI models, as the `ai-train` content signal. This
+	/// also decides whether the path is allowed or disallowed in ai.txt.
+	#[must_use]
+	pub fn ai_train(mut self, allowed: bool) -> Self {
+		self.ai_train = Some(allowed);
+		self
+	}
+
+	/// Whether text and data mining rights are reserved for this content, as per the [TDM
+	/// Reservation Protocol](https://www.w3.org/community/reports/tdmrep/CG-FINAL-tdmrep-20240510/)
+	#[must_use]
+	pub fn tdm_reservation(mut self, reserved: bool) -> Self {
+		self.tdm_reservation = Some(reserved);
+		self
+	}
+
+	fn content_signal_value(&self) -> Option<String> {
+		let signals = [
+			("search", self.search),
+			("ai-input", self.ai_input),
+			("ai-train", self.ai_train)
+		]
+		.into_iter()
+		.filter_map(|(name, val)| {
+			val.map(|val| format!("{name}={}", if val { "yes" } else { "no" }))
+		})
+		.collect::<Vec<_>>();
+
+		(!signals.is_empty()).then(|| signals.join(", "))
+	}
+}
+
+/// A set of per-path [`ContentSignals`], from which all the machine-readable opt-out files that
+/// this crate knows how to generate can be produced: `Content-Signal` rules in robots.txt (through
+/// [`RobotsTxtBuilder::content_policy`]), [ai.txt](https://site.spawning.ai/spawning-ai-txt), and
+/// TDMRep's `tdmrep.json`.
+///
+/// Paths are prefixes, as they are in robots.txt, and more specific paths should come after less
+/// specific ones.
+///
+/// ```rust
+/// use tower_no_ai::{ContentPolicy, ContentSignals, RobotsTxtBuilder};
+///
+/// let policy = ContentPolicy::new()
+///     .path("/", ContentSignals::new().search(true).ai_train(false))
+///     .path("/blog/", ContentSignals::new().ai_input(true).tdm_reservation(true));
+///
+/// let robots_txt = RobotsTxtBuilder::new().content_policy(policy.clone()).build();
+/// assert!(robots_txt.contains("Content-Signal: search=yes, ai-train=no\n"));
+///
+/// let ai_txt = policy.ai_txt();
+/// assert!(ai_txt.contains("Disallow: /\n"));
+/// ```
+///
+/// [`RobotsTxtBuilder::content_policy`]: crate::RobotsTxtBuilder::content_policy
+#[derive(Clone, Debug, Default)]
+pub struct ContentPolicy {
+	paths: Vec<(String, ContentSignals)>
+}
+
+impl ContentPolicy {
+	/// Create a new `Self` with no paths in it
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Attach the given signals to everything under `path`
+	#[must_use]
+	pub fn path(mut self, path: impl Into<String>, signals: ContentSignals) -> Self {
+		self.paths.push((path.into(), signals));
+		self
+	}
+
+	/// Writes the `Content-Signal` group that this policy corresponds to, in the format proposed
+	/// at <https://contentsignals.org>. Signals for `/` apply to the whole site, so they're written
+	/// without a path.
+	pub(crate) fn write_content_signals(&self, txt: &mut String) {
+		let lines = self
+			.paths
+			.iter()
+			.filter_map(|(path, signals)| signals.content_signal_value().map(|val| (path, val)))
+			.map(|(path, val)| match path.as_str() {
+				"/" => format!("Content-Signal: {val}\n"),
+				path => format!("Content-Signal: {path} {val}\n")
+			})
+			.collect::<String>();
+
+		if !lines.is_empty() {
+			_ = write!(txt, "User-Agent: *\n{lines}Allow: /\n");
+		}
+	}
+
+	/// Generate the contents of an ai.txt file, which allows or disallows each path for all agents
+	/// based on its [`ContentSignals::ai_train`] setting
+	pub fn ai_txt(&self) -> String {
+		self.paths
+			.iter()
+			.filter_map(|(path, signals)| signals.ai_train.map(|allowed| (path, allowed)))
+			.fold(String::from("User-Agent: *\n"), |txt, (path, allowed)| {
+				let rule = if allowed { "Allow" } else { "Disallow" };
+				format!("{txt}{rule}: {path}\n")
+			})
+	}
+
+	/// Generate the contents of a `/.well-known/tdmrep.json` file, with an entry for each path that
+	/// has its [`ContentSignals::tdm_reservation`] set
+	pub fn tdmrep_json(&self) -> String {
+		let entries = self
+			.paths
+			.iter()
+			.filter_map(|(path, signals)| signals.tdm_reservation.map(|reserved| (path, reserved)))
+			.map(|(path, reserved)| {
+				// tdmrep locations are patterns rather than prefixes, so we need to add a wildcard
+				let wildcard = if path.ends_with('/') { "*" } else { "" };
+				format!(
+					"{{\"location\":\"{}{wildcard}\",\"tdm-reservation\":{}}}",
+					json_escape(path),
+					u8::from(reserved)
+				)
+			})
+			.collect::<Vec<_>>();
+
+		format!("[{}]", entries.join(","))
+	}
+}
+
+fn json_escape(s: &str) -> String {
+	s.chars()
+		.fold(String::with_capacity(s.len()), |mut escaped, c| {
+			match c {
+				'"' => escaped.push_str("\\\""),
+				'\\' => escaped.push_str("\\\\"),
+				c if c.is_control() => _ = write!(escaped, "\\u{:04x}", u32::from(c)),
+				c => escaped.push(c)
+			}
+			escaped
+		})
+}
@@ -0,0 +1,151 @@
+use std::{
+	fs::{self, File, OpenOptions},
+	io::{self, Write},
+	path::{Path, PathBuf},
+	time::Duration
+};
+
+use crate::Detection;
+
+/// Somewhere for a [`NoAiLayer`] to send every detection that it makes, added to a layer with
+/// [`NoAiLayer::detection_sink`]. A layer can have any number of these, and every one of them sees
+/// every detection.
+///
+/// [`DecisionLog`] is the main sink that this crate provides, which turns each detection into a
+/// line of JSON and can write them to a channel, [stderr], or any writer (like a
+/// [`RotatingFile`]). With the `broadcast` feature, `DetectionBroadcast` sends them to any number
+/// of async tasks instead, and with the `sqlite` feature, `SqliteStats` saves them to a database.
+/// Anything else can implement this itself:
+///
+/// ```rust
+/// use std::{
+///     sync::atomic::{AtomicU64, Ordering},
+///     time::Duration
+/// };
+///
+/// use tower_no_ai::{DecisionLog, Detection, DetectionSink, NoAiLayer};
+///
+/// #[derive(Default)]
+/// struct NonCompliant(AtomicU64);
+///
+/// impl DetectionSink for NonCompliant {
+///     fn record(&self, detection: &Detection<'_>, _: Duration) {
+///         if detection.non_compliant {
+///             self.0.fetch_add(1, Ordering::Relaxed);
+///         }
+///     }
+/// }
+///
+/// let layer = NoAiLayer::new("https://example.com")
+///     .detection_sink(NonCompliant::default())
+///     .detection_sink(DecisionLog::stderr());
+/// ```
+///
+/// [`NoAiLayer`]: crate::NoAiLayer
+/// [`NoAiLayer::detection_sink`]: crate::NoAiLayer::detection_sink
+/// [`DecisionLog`]: crate::DecisionLog
+/// [stderr]: crate::DecisionLog::stderr
+pub trait DetectionSink: Send + Sync + 'static {
+	/// Records `detection`, which was made `since_epoch` after the unix epoch according to the
+	/// layer's [`Clock`]. This is called while the request is being handled, so it shouldn't block
+	/// for any longer than it has to.
+	///
+	/// [`Clock`]: crate::Clock
+	fn record(&self, detection: &Detection<'_>, since_epoch: Duration);
+}
+
+/// A log file which is rotated once it grows past a certain size: the file is renamed to
+/// `<path>.1` (and any older ones to `<path>.2` and so on, up to the number that are
+/// [kept](Self::keep)), and a new one is started in its place. Files are only rotated between
+/// lines, so each one ends with a whole line.
+///
+/// This is meant for [`DecisionLog::to_writer`], but works as a [`Write`] for anything else too.
+///
+/// ```rust,no_run
+/// use tower_no_ai::{DecisionLog, NoAiLayer, RotatingFile};
+///
+/// let file = RotatingFile::open("/var/log/noai.jsonl", 64 * 1024 * 1024)
+///     .unwrap()
+///     .keep(10);
+/// let layer = NoAiLayer::new("https://example.com").detection_sink(DecisionLog::to_writer(file));
+/// ```
+///
+/// [`DecisionLog::to_writer`]: crate::DecisionLog::to_writer
+#[derive(Debug)]
+pub struct RotatingFile {
+	path: PathBuf,
+	file: File,
+	len: u64,
+	max_len: u64,
+	keep: usize,
+	at_line_start: bool
+}
+
+impl RotatingFile {
+	/// Open the file at `path` for appending (creating it if it doesn't exist yet), which is
+	/// rotated once it's more than `max_len` bytes long. By default, 5 old files are kept.
+	pub fn open(path: impl Into<PathBuf>, max_len: u64) -> io::Result<Self> {
+		let path = path.into();
+		let file = append(&path)?;
+		Ok(Self {
+			len: file.metadata()?.len(),
+			path,
+			file,
+			max_len,
+			keep: 5,
+			at_line_start: true
+		})
+	}
+
+	/// Set how many old files to keep around. With 0, the file is just emptied when it's rotated.
+	#[must_use]
+	pub fn keep(mut self, keep: usize) -> Self {
+		self.keep = keep;
+		self
+	}
+
+	fn rotate(&mut self) -> io::Result<()> {
+		if self.keep == 0 {
+			self.file.set_len(0)?;
+		} else {
+			for n in (1..self.keep).rev() {
+				let from = self.numbered(n);
+				if from.exists() {
+					fs::rename(from, self.numbered(n + 1))?;
+				}
+			}
+			fs::rename(&self.path, self.numbered(1))?;
+			self.file = append(&self.path)?;
+		}
+		self.len = 0;
+		Ok(())
+	}
+
+	fn numbered(&self, n: usize) -> PathBuf {
+		let mut path = self.path.clone().into_os_string();
+		path.push(format!(".{n}"));
+		PathBuf::from(path)
+	}
+}
+
+impl Write for RotatingFile {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		if self.at_line_start && self.len >= self.max_len && !buf.is_empty() {
+			self.rotate()?;
+		}
+		let written = self.file.write(buf)?;
+		if let Some(last) = buf[..written].last() {
+			self.at_line_start = *last == b'\n';
+		}
+		self.len += written as u64;
+		Ok(written)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.file.flush()
+	}
+}
+
+fn append(path: &Path) -> io::Result<File> {
+	OpenOptions::new().create(true).append(true).open(path)
+}
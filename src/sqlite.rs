@@ -0,0 +1,254 @@
+use std::{
+	cmp::Reverse,
+	fmt, io,
+	net::IpAddr,
+	path::Path,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		mpsc::{self, SyncSender, TrySendError},
+		Arc, Mutex, PoisonError
+	},
+	thread,
+	time::Duration
+};
+
+use rusqlite::{params, Connection};
+
+use crate::{Action, AgentCategory, AgentStats, Detection, DetectionSink, UNIQUE_IP_DAYS};
+
+const SCHEMA: &str = "
+PRAGMA journal_mode = WAL;
+
+CREATE TABLE IF NOT EXISTS detections (
+	timestamp INTEGER NOT NULL,
+	pattern TEXT NOT NULL,
+	category TEXT NOT NULL,
+	user_agent TEXT NOT NULL,
+	ip TEXT,
+	path TEXT NOT NULL,
+	non_compliant INTEGER NOT NULL,
+	escalated INTEGER NOT NULL,
+	action TEXT,
+	status INTEGER
+);
+CREATE INDEX IF NOT EXISTS detections_by_time ON detections (timestamp);
+CREATE INDEX IF NOT EXISTS detections_by_pattern ON detections (pattern, timestamp);
+
+CREATE TABLE IF NOT EXISTS daily (
+	day TEXT NOT NULL,
+	pattern TEXT NOT NULL,
+	category TEXT NOT NULL,
+	detections INTEGER NOT NULL,
+	non_compliant INTEGER NOT NULL,
+	PRIMARY KEY (day, pattern)
+);
+";
+
+/// A [`DetectionSink`] which saves every detection (and a daily count of them for each agent) to
+/// a SQLite database, so that they're kept across restarts, and can be looked into with any tool
+/// that reads SQLite. This goes along with [`NoAiStats`], which only keeps its counts in memory.
+/// Add it to a layer with [`NoAiLayer::detection_sink`].
+///
+/// The database has two tables:
+/// - `detections`, with a row for each detection. Its columns are named after the fields of
+///   [`DecisionLog`]'s lines, with `timestamp` in milliseconds since the unix epoch (according to
+///   the layer's [`Clock`]), and `non_compliant` and `escalated` as 0 or 1.
+/// - `daily`, with the number of `detections` (and how many of them were `non_compliant`) of each
+///   `pattern` on each `day` (as `YYYY-MM-DD`, in UTC), along with the pattern's `category`
+///
+/// Detections are handed off to a background thread without ever waiting, which writes everything
+/// that's come in since its last write in a single transaction. If it falls too far behind (or a
+/// write fails), detections are dropped and counted in [`Self::dropped`] instead of slowing down
+/// requests. Since this needs to be able to spawn a thread, it can't be used on
+/// `wasm32-unknown-unknown`.
+///
+/// Rows are never deleted, so the `detections` table keeps growing. Anything that's no longer
+/// needed can be deleted with SQL (e.g. `DELETE FROM detections WHERE timestamp < ...`) while the
+/// layer is running.
+///
+/// Requires the `sqlite` feature.
+///
+/// ```rust,no_run
+/// use tower_no_ai::{NoAiLayer, SqliteStats};
+///
+/// let stats = SqliteStats::open("/var/lib/noai/stats.db").unwrap();
+/// let layer = NoAiLayer::new("https://example.com").detection_sink(stats.clone());
+///
+/// // ...and then later on, even after a restart
+/// for (agent, agent_stats) in stats.agents().unwrap() {
+///     println!("{agent}: {} requests", agent_stats.detections);
+/// }
+/// ```
+///
+/// [`NoAiStats`]: crate::NoAiStats
+/// [`NoAiLayer::detection_sink`]: crate::NoAiLayer::detection_sink
+/// [`DecisionLog`]: crate::DecisionLog
+/// [`Clock`]: crate::Clock
+#[derive(Clone)]
+pub struct SqliteStats {
+	sender: SyncSender<Row>,
+	dropped: Arc<AtomicU64>,
+	// for reading back out, since the writer's connection belongs to its thread
+	reader: Arc<Mutex<Connection>>
+}
+
+/// A detection waiting to be written
+struct Row {
+	timestamp: u64,
+	pattern: String,
+	category: AgentCategory,
+	user_agent: String,
+	ip: Option<IpAddr>,
+	path: String,
+	non_compliant: bool,
+	escalated: bool,
+	action: Option<Action>
+}
+
+impl SqliteStats {
+	/// Open the database at `path` (creating it and its tables if they don't exist yet), and start
+	/// the thread which writes to it
+	pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+		let path = path.as_ref();
+		let mut writer = Connection::open(path).map_err(other)?;
+		writer.execute_batch(SCHEMA).map_err(other)?;
+		let reader = Connection::open(path).map_err(other)?;
+
+		let (sender, rows) = mpsc::sync_channel(4096);
+		let dropped = Arc::<AtomicU64>::default();
+		let write_dropped = Arc::clone(&dropped);
+		thread::spawn(move || {
+			let mut batch = Vec::new();
+			while let Ok(row) = rows.recv() {
+				batch.push(row);
+				batch.extend(rows.try_iter());
+				// there's nowhere to report this to, and the next write might work out better
+				if write(&mut writer, &batch).is_err() {
+					write_dropped.fetch_add(batch.len() as u64, Ordering::Relaxed);
+				}
+				batch.clear();
+			}
+		});
+
+		Ok(Self {
+			sender,
+			dropped,
+			reader: Arc::new(Mutex::new(reader))
+		})
+	}
+
+	/// The number of detections which were dropped because the thread writing them had fallen
+	/// behind, or because writing them failed
+	pub fn dropped(&self) -> u64 {
+		self.dropped.load(Ordering::Relaxed)
+	}
+
+	/// Returns the statistics for every agent in the database, like [`NoAiStats::agents`] does for
+	/// the ones it's seen, sorted by their number of detections (most detections first). The number
+	/// of unique IPs is counted exactly, over the last [`UNIQUE_IP_DAYS`] days before the most
+	/// recent detection.
+	///
+	/// [`NoAiStats::agents`]: crate::NoAiStats::agents
+	pub fn agents(&self) -> io::Result<Vec<(String, AgentStats)>> {
+		let reader = self.reader.lock().unwrap_or_else(PoisonError::into_inner);
+		let mut query = reader
+			.prepare_cached(
+				"SELECT daily.pattern, SUM(daily.detections), SUM(daily.non_compliant), (
+					SELECT COUNT(DISTINCT ip) FROM detections
+					WHERE detections.pattern = daily.pattern AND timestamp >= (
+						SELECT MAX(timestamp) FROM detections
+					) - ?2
+				)
+				FROM daily WHERE category != ?1 GROUP BY daily.pattern"
+			)
+			.map_err(other)?;
+		let window = UNIQUE_IP_DAYS as u64 * 24 * 60 * 60 * 1000;
+		let mut agents = query
+			.query_map(params![AgentCategory::Referral.name(), window], |row| {
+				Ok((row.get(0)?, AgentStats {
+					detections: row.get(1)?,
+					non_compliant: row.get(2)?,
+					unique_ips: row.get(3)?
+				}))
+			})
+			.and_then(Iterator::collect::<Result<Vec<_>, _>>)
+			.map_err(other)?;
+
+		agents.sort_unstable_by_key(|(_, stats): &(String, AgentStats)| Reverse(stats.detections));
+		Ok(agents)
+	}
+}
+
+impl DetectionSink for SqliteStats {
+	fn record(&self, detection: &Detection<'_>, since_epoch: Duration) {
+		let row = Row {
+			timestamp: since_epoch.as_millis() as u64,
+			pattern: detection.pattern.to_owned(),
+			category: detection.category,
+			user_agent: String::from_utf8_lossy(detection.user_agent).into_owned(),
+			ip: detection.client_ip,
+			path: detection.path.to_owned(),
+			non_compliant: detection.non_compliant,
+			escalated: detection.escalated,
+			action: detection.action.cloned()
+		};
+		match self.sender.try_send(row) {
+			Ok(()) | Err(TrySendError::Disconnected(_)) => (),
+			Err(TrySendError::Full(_)) => {
+				self.dropped.fetch_add(1, Ordering::Relaxed);
+			}
+		}
+	}
+}
+
+impl fmt::Debug for SqliteStats {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("SqliteStats")
+			.field("dropped", &self.dropped())
+			.finish_non_exhaustive()
+	}
+}
+
+fn write(conn: &mut Connection, rows: &[Row]) -> rusqlite::Result<()> {
+	let tx = conn.transaction()?;
+	{
+		let mut detection = tx.prepare_cached(
+			"INSERT INTO detections VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"
+		)?;
+		let mut daily = tx.prepare_cached(
+			"INSERT INTO daily VALUES (date(?1 / 1000, 'unixepoch'), ?2, ?3, 1, ?4)
+			ON CONFLICT (day, pattern) DO UPDATE SET
+				detections = detections + 1,
+				non_compliant = non_compliant + excluded.non_compliant"
+		)?;
+		for row in rows {
+			let status = match &row.action {
+				Some(Action::Status(status)) => Some(status.as_u16()),
+				_ => None
+			};
+			detection.execute(params![
+				row.timestamp,
+				row.pattern,
+				row.category.name(),
+				row.user_agent,
+				row.ip.map(|ip| ip.to_string()),
+				row.path,
+				row.non_compliant,
+				row.escalated,
+				row.action.as_ref().map(Action::name),
+				status
+			])?;
+			daily.execute(params![
+				row.timestamp,
+				row.pattern,
+				row.category.name(),
+				row.non_compliant
+			])?;
+		}
+	}
+	tx.commit()
+}
+
+fn other(e: rusqlite::Error) -> io::Error {
+	io::Error::new(io::ErrorKind::Other, e)
+}
@@ -0,0 +1,417 @@
+use std::{
+	cmp::Reverse,
+	collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+	hash::{Hash, Hasher},
+	net::IpAddr,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Mutex, PoisonError
+	},
+	time::Duration
+};
+
+use crate::{AgentCategory, Detection};
+
+/// A collector of statistics about the agents that a [`NoAiLayer`] has detected. This is a cheap
+/// handle to shared state, so it can be cloned into the layer with [`NoAiLayer::stats`] and kept
+/// around elsewhere (e.g. in a handler for an internal metrics endpoint) to read them back out.
+///
+/// ```rust
+/// use tower_no_ai::{NoAiLayer, NoAiStats};
+///
+/// let stats = NoAiStats::new();
+/// let layer = NoAiLayer::new("https://example.com").stats(stats.clone());
+///
+/// // ...and then later on
+/// for (agent, agent_stats) in stats.agents() {
+///     println!(
+///         "{agent}: {} requests from about {} addresses",
+///         agent_stats.detections, agent_stats.unique_ips
+///     );
+/// }
+/// ```
+///
+/// [`NoAiLayer`]: crate::NoAiLayer
+/// [`NoAiLayer::stats`]: crate::NoAiLayer::stats
+#[derive(Clone, Default)]
+pub struct NoAiStats {
+	agents: Arc<Mutex<HashMap<String, AgentEntry>>>,
+	// kept apart from the agents, since these are people rather than agents
+	referrals: Arc<Mutex<HashMap<String, AgentEntry>>>,
+	// the number of blocks on each of the last `BLOCK_HISTORY_DAYS` days that had any, oldest first
+	blocks: Arc<Mutex<VecDeque<(u64, u64)>>>,
+	// the most requested paths, of which only `TRACKED_PATHS` are kept
+	paths: Arc<Mutex<HashMap<String, u64>>>,
+	// the most recent day that anything was recorded on, in days since the unix epoch according to
+	// the layer's clock
+	latest_day: Arc<AtomicU64>
+}
+
+/// The statistics collected for a single agent pattern by [`NoAiStats`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AgentStats {
+	/// The number of requests from this agent that were detected
+	pub detections: u64,
+	/// The number of those requests that were for a path which the generated robots.txt disallows
+	/// this agent from accessing
+	pub non_compliant: u64,
+	/// An estimate of how many different IP addresses this agent's requests came from over the
+	/// last [`UNIQUE_IP_DAYS`] days, as in [`NoAiStats::unique_ips`]
+	pub unique_ips: u64
+}
+
+/// How many days of IP addresses [`NoAiStats`] keeps track of for [`NoAiStats::unique_ips`]
+pub const UNIQUE_IP_DAYS: usize = 7;
+
+/// How many days of blocks [`NoAiStats`] keeps track of for [`NoAiStats::blocks_by_day`]
+pub const BLOCK_HISTORY_DAYS: usize = 30;
+
+// how many different paths are counted for `NoAiStats::top_paths`
+const TRACKED_PATHS: usize = 256;
+
+#[derive(Default)]
+struct AgentEntry {
+	stats: AgentStats,
+	// one sketch for each of the last `UNIQUE_IP_DAYS` days that had a detection with a known IP,
+	// oldest first
+	ips: VecDeque<(u64, UniqueIps)>
+}
+
+impl AgentEntry {
+	fn stats(&self, latest_day: u64) -> AgentStats {
+		AgentStats {
+			unique_ips: self.unique_ips(latest_day, UNIQUE_IP_DAYS),
+			..self.stats
+		}
+	}
+
+	fn unique_ips(&self, latest_day: u64, days: usize) -> u64 {
+		let mut merged = UniqueIps::default();
+		for (day, ips) in &self.ips {
+			if latest_day - day < days as u64 {
+				merged.merge(ips);
+			}
+		}
+		merged.estimate()
+	}
+}
+
+impl NoAiStats {
+	/// Create a new `Self` with nothing recorded yet
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub(crate) fn record(&self, detection: &Detection<'_>, since_epoch: Duration) {
+		let day = since_epoch.as_secs() / SECS_PER_DAY;
+		let latest_day = self.latest_day.fetch_max(day, Ordering::Relaxed).max(day);
+		if detection
+			.action
+			.is_some_and(|action| !action.passes_through())
+		{
+			self.record_block(day, latest_day);
+		}
+		let agents = match detection.category {
+			AgentCategory::Referral => &self.referrals,
+			_ => {
+				self.record_path(detection.path);
+				&self.agents
+			}
+		};
+		let mut agents = agents.lock().unwrap_or_else(PoisonError::into_inner);
+
+		// only allocate a new key if we haven't seen this agent yet
+		let entry = match agents.get_mut(detection.pattern) {
+			Some(entry) => entry,
+			None => agents.entry(detection.pattern.to_owned()).or_default()
+		};
+
+		entry.stats.detections += 1;
+		if detection.non_compliant {
+			entry.stats.non_compliant += 1;
+		}
+
+		// an address is only worth recording if its day is still in the window, which it might not
+		// be if the clock has gone backwards
+		let ip = detection
+			.client_ip
+			.filter(|_| latest_day - day < UNIQUE_IP_DAYS as u64);
+		if let Some(ip) = ip {
+			// forget about the days which have fallen out of the window
+			while entry
+				.ips
+				.front()
+				.is_some_and(|(oldest, _)| latest_day - oldest >= UNIQUE_IP_DAYS as u64)
+			{
+				entry.ips.pop_front();
+			}
+
+			match entry.ips.iter().position(|(d, _)| *d >= day) {
+				Some(i) if entry.ips[i].0 == day => entry.ips[i].1.insert(ip),
+				// the clock went backwards, so this day's sketch needs to go before newer ones
+				Some(i) => entry.ips.insert(i, (day, UniqueIps::with(ip))),
+				None => entry.ips.push_back((day, UniqueIps::with(ip)))
+			}
+		}
+	}
+
+	fn record_block(&self, day: u64, latest_day: u64) {
+		if latest_day - day >= BLOCK_HISTORY_DAYS as u64 {
+			return;
+		}
+		let mut blocks = self.blocks.lock().unwrap_or_else(PoisonError::into_inner);
+		while blocks
+			.front()
+			.is_some_and(|(oldest, _)| latest_day - oldest >= BLOCK_HISTORY_DAYS as u64)
+		{
+			blocks.pop_front();
+		}
+		match blocks.iter().position(|(d, _)| *d >= day) {
+			Some(i) if blocks[i].0 == day => blocks[i].1 += 1,
+			Some(i) => blocks.insert(i, (day, 1)),
+			None => blocks.push_back((day, 1))
+		}
+	}
+
+	/// Counts a request for `path`, with the space-saving algorithm: once there are as many paths
+	/// as are tracked, a new one takes the place (and the count) of the least requested one, so
+	/// that paths which are requested a lot always make it in, even if they show up late
+	fn record_path(&self, path: &str) {
+		let mut paths = self.paths.lock().unwrap_or_else(PoisonError::into_inner);
+		if let Some(count) = paths.get_mut(path) {
+			*count += 1;
+			return;
+		}
+		let count = if paths.len() < TRACKED_PATHS {
+			0
+		} else {
+			let Some((least, count)) = paths
+				.iter()
+				.min_by_key(|(_, count)| **count)
+				.map(|(path, count)| (path.clone(), *count))
+			else {
+				return;
+			};
+			paths.remove(&least);
+			count
+		};
+		paths.insert(path.to_owned(), count + 1);
+	}
+
+	/// Returns the statistics for the given agent pattern, if it's been seen yet
+	pub fn agent(&self, pattern: &str) -> Option<AgentStats> {
+		self.agents
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get(pattern)
+			.map(|entry| entry.stats(self.latest_day.load(Ordering::Relaxed)))
+	}
+
+	/// Returns an estimate of how many different IP addresses the given agent pattern's requests
+	/// came from over the last `days` days (up to [`UNIQUE_IP_DAYS`]), if it's been seen yet. Days
+	/// are counted back from the day of the most recent detection of any agent, by the layer's
+	/// [`Clock`], and requests whose IP isn't known (see [`ClientIpSource`]) aren't counted.
+	///
+	/// This is estimated with a HyperLogLog sketch rather than by storing every address, so it
+	/// takes a fixed 1KiB per agent per day no matter how many addresses there are, and is usually
+	/// within a few percent of the true count.
+	///
+	/// ```rust
+	/// use tower_no_ai::NoAiStats;
+	///
+	/// let stats = NoAiStats::new();
+	/// // ...once it's been given to a layer that's seen some traffic
+	/// if let Some(count) = stats.unique_ips("GPTBot", 7) {
+	///     println!("GPTBot used about {count} addresses this week");
+	/// }
+	/// ```
+	///
+	/// [`Clock`]: crate::Clock
+	/// [`ClientIpSource`]: crate::ClientIpSource
+	pub fn unique_ips(&self, pattern: &str, days: usize) -> Option<u64> {
+		let latest_day = self.latest_day.load(Ordering::Relaxed);
+		self.agents
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get(pattern)
+			.map(|entry| entry.unique_ips(latest_day, days.min(UNIQUE_IP_DAYS)))
+	}
+
+	/// Returns the statistics for every agent that's been seen so far, sorted by their number of
+	/// detections (most detections first)
+	pub fn agents(&self) -> Vec<(String, AgentStats)> {
+		self.sorted(&self.agents)
+	}
+
+	/// Returns the statistics for every AI chat product (from [`AI_REFERRERS`]) that has referred
+	/// people to the site so far, with [`NoAiLayer::track_ai_referrals`], sorted by their number of
+	/// referrals (most first). These aren't included in [`Self::agents`].
+	///
+	/// [`AI_REFERRERS`]: crate::AI_REFERRERS
+	/// [`NoAiLayer::track_ai_referrals`]: crate::NoAiLayer::track_ai_referrals
+	pub fn referrals(&self) -> Vec<(String, AgentStats)> {
+		self.sorted(&self.referrals)
+	}
+
+	fn sorted(&self, agents: &Mutex<HashMap<String, AgentEntry>>) -> Vec<(String, AgentStats)> {
+		let latest_day = self.latest_day.load(Ordering::Relaxed);
+		let mut agents = agents
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.iter()
+			.map(|(pattern, entry)| (pattern.clone(), entry.stats(latest_day)))
+			.collect::<Vec<_>>();
+
+		agents.sort_unstable_by_key(|(_, stats)| Reverse(stats.detections));
+		agents
+	}
+
+	/// Returns the number of requests that were blocked (rather than passed on to the inner
+	/// service, e.g. because of the layer's [`Mode`] or with [`Action::Watermark`]) on each of the
+	/// last [`BLOCK_HISTORY_DAYS`] days, as days since the unix epoch, oldest first. Days are
+	/// counted back from the day of the most recent detection, by the layer's [`Clock`], and days
+	/// without any blocks are left out.
+	///
+	/// ```rust
+	/// # #[cfg(feature = "test-util")] {
+	/// use tower_layer::Layer;
+	/// use tower_no_ai::{
+	///     test_util::{agent_request, call, Passthrough},
+	///     Action, NoAiLayer, NoAiStats
+	/// };
+	///
+	/// let stats = NoAiStats::new();
+	/// let mut watermarking = NoAiLayer::new("https://example.com")
+	///     .action(Action::Watermark)
+	///     .stats(stats.clone())
+	///     .layer(Passthrough);
+	/// let mut redirecting = NoAiLayer::new("https://example.com")
+	///     .stats(stats.clone())
+	///     .layer(Passthrough);
+	///
+	/// call(&mut watermarking, agent_request("GPTBot")).unwrap();
+	/// assert!(stats.blocks_by_day().is_empty());
+	///
+	/// call(&mut redirecting, agent_request("GPTBot")).unwrap();
+	/// let blocks = stats.blocks_by_day();
+	/// assert_eq!(blocks.len(), 1);
+	/// assert_eq!(blocks[0].1, 1);
+	///
+	/// // both requests were still detected
+	/// assert_eq!(stats.agents()[0].1.detections, 2);
+	/// # }
+	/// ```
+	///
+	/// [`Mode`]: crate::Mode
+	/// [`Action::Watermark`]: crate::Action::Watermark
+	/// [`Clock`]: crate::Clock
+	pub fn blocks_by_day(&self) -> Vec<(u64, u64)> {
+		let latest_day = self.latest_day.load(Ordering::Relaxed);
+		self.blocks
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.iter()
+			.filter(|(day, _)| latest_day - day < BLOCK_HISTORY_DAYS as u64)
+			.copied()
+			.collect()
+	}
+
+	/// Returns the `n` paths that agents requested the most, along with about how many times they
+	/// requested each one, most requested first. Only a few hundred paths are counted at a time, so
+	/// for paths that weren't requested much, the counts can be too high.
+	pub fn top_paths(&self, n: usize) -> Vec<(String, u64)> {
+		let mut paths = self
+			.paths
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.iter()
+			.map(|(path, count)| (path.clone(), *count))
+			.collect::<Vec<_>>();
+
+		paths.sort_unstable_by_key(|(_, count)| Reverse(*count));
+		paths.truncate(n);
+		paths
+	}
+
+	/// Returns the agents which have requested at least one path that the generated robots.txt
+	/// disallows them from accessing, along with how many times they've done so
+	pub fn non_compliant_agents(&self) -> Vec<(String, u64)> {
+		let mut agents = self
+			.agents
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.iter()
+			.filter(|(_, entry)| entry.stats.non_compliant > 0)
+			.map(|(pattern, entry)| (pattern.clone(), entry.stats.non_compliant))
+			.collect::<Vec<_>>();
+
+		agents.sort_unstable_by_key(|(_, count)| Reverse(*count));
+		agents
+	}
+}
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+// the number of bits of each address's hash that pick its register, which makes for a standard
+// error of about 1.04 / sqrt(2^10), or 3.25%
+const INDEX_BITS: u32 = 10;
+const REGISTERS: usize = 1 << INDEX_BITS;
+
+/// A HyperLogLog sketch of a set of IP addresses, which can estimate how many different addresses
+/// it's seen
+#[derive(Clone)]
+struct UniqueIps(Box<[u8; REGISTERS]>);
+
+impl Default for UniqueIps {
+	fn default() -> Self {
+		Self(Box::new([0; REGISTERS]))
+	}
+}
+
+impl UniqueIps {
+	fn with(ip: IpAddr) -> Self {
+		let mut ips = Self::default();
+		ips.insert(ip);
+		ips
+	}
+
+	fn insert(&mut self, ip: IpAddr) {
+		// `DefaultHasher::new` always uses the same keys, so an address always lands in the same
+		// register and sketches from different days can be merged
+		let mut hasher = DefaultHasher::new();
+		ip.hash(&mut hasher);
+		let hash = hasher.finish();
+
+		let index = (hash >> (64 - INDEX_BITS)) as usize;
+		// the position of the first set bit in what's left, which is at most `64 - INDEX_BITS + 1`
+		let rank = ((hash << INDEX_BITS) | (1 << (INDEX_BITS - 1))).leading_zeros() as u8 + 1;
+		self.0[index] = self.0[index].max(rank);
+	}
+
+	fn merge(&mut self, other: &Self) {
+		for (register, other) in self.0.iter_mut().zip(other.0.iter()) {
+			*register = (*register).max(*other);
+		}
+	}
+
+	fn estimate(&self) -> u64 {
+		let m = REGISTERS as f64;
+		let alpha = 0.7213 / (1.0 + 1.079 / m);
+		let sum = self
+			.0
+			.iter()
+			.map(|register| 2f64.powi(-i32::from(*register)))
+			.sum::<f64>();
+		let estimate = alpha * m * m / sum;
+
+		// the raw estimate is biased for small sets, which are better counted by how many
+		// registers are still empty
+		let empty = self.0.iter().filter(|register| **register == 0).count();
+		if estimate <= 2.5 * m && empty > 0 {
+			(m * (m / empty as f64).ln()).round() as u64
+		} else {
+			estimate.round() as u64
+		}
+	}
+}
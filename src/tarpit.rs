@@ -0,0 +1,268 @@
+use std::{
+	future::Future,
+	pin::Pin,
+	sync::Arc,
+	task::{ready, Context, Poll},
+	time::Duration
+};
+
+use bytes::{Buf, Bytes};
+use http::{header::CONTENT_LENGTH, Method, Request, Response};
+use http_body::{Body, Frame, SizeHint};
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+	action::garbage,
+	check::{BoxFuture, Sleep}
+};
+
+/// The response extension that a [`NoAiService`] marks its responses with when they should be
+/// drip-fed to the agent, because of [`Action::Tarpit`]
+///
+/// [`NoAiService`]: crate::NoAiService
+/// [`Action::Tarpit`]: crate::Action::Tarpit
+#[derive(Clone)]
+pub(crate) struct Tarpitted;
+
+/// A [`Layer`] which slowly drip-feeds meaningless text to the agents which a [`NoAiLayer`] has
+/// decided to [tarpit](crate::Action::Tarpit), a few bytes at a time, so that each one of them
+/// ties up a connection for as long as possible while getting nothing of value out of it. Every
+/// other response is passed through untouched.
+///
+/// This needs to be outside of the [`NoAiLayer`], since it finds the responses to replace by what
+/// the [`NoAiLayer`] marks them with. Without it, tarpitted agents just get an empty `200 OK`.
+/// There's no timer built in (since this crate doesn't depend on any async runtime), so the layer
+/// needs to be given a function which sleeps for a duration, such as `tokio::time::sleep`.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use axum::routing::{get, Router};
+/// use tower_no_ai::{Action, NoAiLayer, TarpitLayer};
+///
+/// # async fn sleep(_: Duration) {}
+/// let router: Router = Router::new()
+///     .route("/", get(|| async { "Hello, World!" }))
+///     .layer(NoAiLayer::new("https://example.com").action(Action::Tarpit))
+///     // layers that are added later go around the earlier ones
+///     .layer(TarpitLayer::new(sleep).chunk(8, Duration::from_secs(2)));
+/// ```
+///
+/// [`NoAiLayer`]: crate::NoAiLayer
+#[derive(Clone)]
+pub struct TarpitLayer {
+	config: Arc<TarpitConfig>
+}
+
+#[derive(Clone)]
+struct TarpitConfig {
+	sleep: Arc<Sleep>,
+	chunk: usize,
+	every: Duration,
+	len: usize
+}
+
+impl TarpitLayer {
+	/// Create a new `Self` which waits between chunks with `sleep`, which should return a future
+	/// that completes once the given duration has passed (like `tokio::time::sleep`). By default,
+	/// 16 bytes are sent every second, up to 8 KiB in total.
+	pub fn new<S, SleepFut>(sleep: S) -> Self
+	where
+		S: Fn(Duration) -> SleepFut + Send + Sync + 'static,
+		SleepFut: Future<Output = ()> + Send + 'static
+	{
+		Self {
+			config: Arc::new(TarpitConfig {
+				sleep: Arc::new(move |duration| Box::pin(sleep(duration))),
+				chunk: 16,
+				every: Duration::from_secs(1),
+				len: 8 * 1024
+			})
+		}
+	}
+
+	/// Send `bytes` bytes at a time, waiting for `every` between each chunk
+	///
+	/// # Panics
+	///
+	/// If `bytes` is 0
+	#[must_use]
+	pub fn chunk(mut self, bytes: usize, every: Duration) -> Self {
+		assert!(bytes > 0, "a tarpit can't send chunks of 0 bytes");
+		let config = self.config_mut();
+		config.chunk = bytes;
+		config.every = every;
+		self
+	}
+
+	/// Set how many bytes are sent in total before the response ends. This is capped at 1 MiB,
+	/// like [`Action::Garbage`].
+	///
+	/// [`Action::Garbage`]: crate::Action::Garbage
+	#[must_use]
+	pub fn len(mut self, len: usize) -> Self {
+		self.config_mut().len = len;
+		self
+	}
+
+	fn config_mut(&mut self) -> &mut TarpitConfig {
+		Arc::make_mut(&mut self.config)
+	}
+}
+
+impl<S> Layer<S> for TarpitLayer {
+	type Service = TarpitService<S>;
+	fn layer(&self, inner: S) -> Self::Service {
+		Self::Service {
+			inner,
+			config: Arc::clone(&self.config)
+		}
+	}
+}
+
+/// The service produced by [`TarpitLayer`]
+#[derive(Clone)]
+pub struct TarpitService<S> {
+	inner: S,
+	config: Arc<TarpitConfig>
+}
+
+impl<S, ReqBody, RespBody> Service<Request<ReqBody>> for TarpitService<S>
+where
+	S: Service<Request<ReqBody>, Response = Response<RespBody>>
+{
+	type Error = S::Error;
+	type Future = TarpitFut<S::Future>;
+	type Response = Response<TarpitBody<RespBody>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+		// a response to a HEAD request has no body to drip
+		let config = Some(Arc::clone(&self.config)).filter(|_| req.method() != Method::HEAD);
+		TarpitFut {
+			future: self.inner.call(req),
+			config
+		}
+	}
+}
+
+pin_project! {
+	/// The Future type that [`TarpitService::call`] produces
+	pub struct TarpitFut<F> {
+		#[pin]
+		future: F,
+		config: Option<Arc<TarpitConfig>>
+	}
+}
+
+impl<F, RespBody, Err> Future for TarpitFut<F>
+where
+	F: Future<Output = Result<Response<RespBody>, Err>>
+{
+	type Output = Result<Response<TarpitBody<RespBody>>, Err>;
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		let this = self.project();
+		let mut response = ready!(this.future.poll(cx))?;
+
+		let drip = this
+			.config
+			.take()
+			.filter(|_| response.extensions_mut().remove::<Tarpitted>().is_some())
+			.map(|config| {
+				// the agent shouldn't know how long it's going to be waiting for
+				response.headers_mut().remove(CONTENT_LENGTH);
+				Box::new(Drip {
+					rest: garbage(config.len),
+					waiting: None,
+					config
+				})
+			});
+
+		Poll::Ready(Ok(response.map(|inner| TarpitBody { inner, drip })))
+	}
+}
+
+pin_project! {
+	/// The body of a response from a [`TarpitService`], which is either the inner service's body,
+	/// or garbage that's sent a chunk at a time if the agent is being tarpitted
+	pub struct TarpitBody<B> {
+		#[pin]
+		inner: B,
+		drip: Option<Box<Drip>>
+	}
+}
+
+struct Drip {
+	config: Arc<TarpitConfig>,
+	// the garbage that hasn't been sent yet
+	rest: Bytes,
+	// the wait before the next chunk can be sent
+	waiting: Option<BoxFuture<()>>
+}
+
+impl<B: From<Bytes>> From<Bytes> for TarpitBody<B> {
+	fn from(bytes: Bytes) -> Self {
+		Self {
+			inner: B::from(bytes),
+			drip: None
+		}
+	}
+}
+
+impl<B> Body for TarpitBody<B>
+where
+	B: Body,
+	B::Data: Buf
+{
+	type Data = Bytes;
+	type Error = B::Error;
+
+	fn poll_frame(
+		self: Pin<&mut Self>,
+		cx: &mut Context<'_>
+	) -> Poll<Option<Result<Frame<Bytes>, B::Error>>> {
+		let this = self.project();
+		let Some(drip) = this.drip else {
+			return this.inner.poll_frame(cx).map(|frame| {
+				frame.map(|frame| {
+					frame.map(|frame| {
+						frame.map_data(|mut data| data.copy_to_bytes(data.remaining()))
+					})
+				})
+			});
+		};
+
+		if let Some(waiting) = &mut drip.waiting {
+			ready!(waiting.as_mut().poll(cx));
+			drip.waiting = None;
+		}
+		if drip.rest.is_empty() {
+			return Poll::Ready(None);
+		}
+
+		let chunk = drip.rest.split_to(drip.config.chunk.min(drip.rest.len()));
+		if !drip.rest.is_empty() {
+			drip.waiting = Some((drip.config.sleep)(drip.config.every));
+		}
+		Poll::Ready(Some(Ok(Frame::data(chunk))))
+	}
+
+	fn is_end_stream(&self) -> bool {
+		match &self.drip {
+			Some(drip) => drip.rest.is_empty(),
+			None => self.inner.is_end_stream()
+		}
+	}
+
+	fn size_hint(&self) -> SizeHint {
+		match &self.drip {
+			Some(_) => SizeHint::default(),
+			None => self.inner.size_hint()
+		}
+	}
+}
@@ -0,0 +1,186 @@
+//! Helpers for testing a service stack that a [`NoAiLayer`] is part of, e.g. to make sure that it
+//! keeps blocking the agents you care about as the stack changes. Requires the `test-util`
+//! feature.
+//!
+//! Put a [`Passthrough`] at the bottom of the stack instead of your real handlers, and then use
+//! [`assert_blocked!`] and [`assert_passed!`] to check which requests make it through to it:
+//!
+//! ```rust
+//! # #[cfg(feature = "test-util")] {
+//! use tower_layer::Layer;
+//! use tower_no_ai::{
+//!     assert_blocked, assert_passed,
+//!     test_util::{agent_request, browser_request, Passthrough},
+//!     NoAiLayer
+//! };
+//!
+//! let mut service = NoAiLayer::new("https://example.com")
+//!     .remove_agents(["Applebot"])
+//!     .layer(Passthrough);
+//!
+//! assert_blocked!(service, agent_request("GPTBot"));
+//! assert_passed!(service, agent_request("Applebot"));
+//! assert_passed!(service, browser_request());
+//! # }
+//! ```
+//!
+//! [`NoAiLayer`]: crate::NoAiLayer
+//! [`assert_blocked!`]: crate::assert_blocked
+//! [`assert_passed!`]: crate::assert_passed
+
+use std::{
+	convert::Infallible,
+	future::{ready, Future, Ready},
+	pin::pin,
+	sync::Arc,
+	task::{Context, Poll, Wake, Waker}
+};
+
+use bytes::Bytes;
+use http::{Request, Response};
+use http_body_util::Full;
+use tower_service::Service;
+
+use crate::AI_AGENTS;
+
+/// Returns a `GET /` request with a User-Agent containing `pattern`, in the same format that most
+/// crawlers use
+pub fn agent_request(pattern: &str) -> Request<()> {
+	Request::get("/")
+		.header(
+			"user-agent",
+			format!("Mozilla/5.0 (compatible; {pattern}/1.0; +https://example.com/bot)")
+		)
+		.body(())
+		.unwrap()
+}
+
+/// Returns an [`agent_request`] for every pattern in [`AI_AGENTS`], along with that pattern
+pub fn agent_requests() -> impl Iterator<Item = (&'static str, Request<()>)> {
+	AI_AGENTS
+		.iter()
+		.map(|pattern| (*pattern, agent_request(pattern)))
+}
+
+/// Returns a `GET /` request with the headers that a current desktop Firefox would send, which
+/// shouldn't be blocked by anything (including [`HeaderHeuristics`])
+///
+/// [`HeaderHeuristics`]: crate::HeaderHeuristics
+pub fn browser_request() -> Request<()> {
+	Request::get("/")
+		.header(
+			"user-agent",
+			"Mozilla/5.0 (X11; Linux x86_64; rv:131.0) Gecko/20100101 Firefox/131.0"
+		)
+		.header(
+			"accept",
+			"text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+		)
+		.header("accept-language", "en-US,en;q=0.5")
+		.header("accept-encoding", "gzip, deflate, br, zstd")
+		.header("sec-fetch-dest", "document")
+		.header("sec-fetch-mode", "navigate")
+		.header("sec-fetch-site", "none")
+		.body(())
+		.unwrap()
+}
+
+/// A service which answers every request with an empty `200 OK` that has a [`Passed`] extension,
+/// so that the assertions in this module can tell whether a request made it all the way through
+/// the stack above it
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Passthrough;
+
+/// The response extension that [`Passthrough`] adds to all of its responses
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Passed;
+
+impl<B> Service<Request<B>> for Passthrough {
+	type Error = Infallible;
+	type Future = Ready<Result<Self::Response, Infallible>>;
+	type Response = Response<Full<Bytes>>;
+
+	fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn call(&mut self, _: Request<B>) -> Self::Future {
+		let mut response = Response::new(Full::default());
+		response.extensions_mut().insert(Passed);
+		ready(Ok(response))
+	}
+}
+
+/// Sends the given request through the given service and returns its response, without needing an
+/// async runtime. This is only meant for stacks which don't wait on any IO (like those built on
+/// [`Passthrough`]), since it just keeps polling until the response is ready.
+pub fn call<S, B>(service: &mut S, req: Request<B>) -> Result<S::Response, S::Error>
+where
+	S: Service<Request<B>>
+{
+	struct NoopWaker;
+
+	impl Wake for NoopWaker {
+		fn wake(self: Arc<Self>) {}
+	}
+
+	let waker = Waker::from(Arc::new(NoopWaker));
+	let mut cx = Context::from_waker(&waker);
+
+	loop {
+		match service.poll_ready(&mut cx) {
+			Poll::Ready(result) => break result?,
+			Poll::Pending => std::thread::yield_now()
+		}
+	}
+
+	let mut future = pin!(service.call(req));
+	loop {
+		match future.as_mut().poll(&mut cx) {
+			Poll::Ready(result) => return result,
+			Poll::Pending => std::thread::yield_now()
+		}
+	}
+}
+
+/// Returns whether the given response came from a [`Passthrough`]
+pub fn passed<B>(response: &Response<B>) -> bool {
+	response.extensions().get::<Passed>().is_some()
+}
+
+/// Asserts that sending the given request through the given service (with a [`Passthrough`] at
+/// the bottom) doesn't make it through to the [`Passthrough`]. Requires the `test-util` feature.
+///
+/// [`Passthrough`]: crate::test_util::Passthrough
+#[macro_export]
+macro_rules! assert_blocked {
+	($service:expr, $req:expr $(,)?) => {{
+		let response = $crate::test_util::call(&mut $service, $req)
+			.unwrap_or_else(|_| panic!("the service returned an error"));
+		assert!(
+			!$crate::test_util::passed(&response),
+			"expected {} to be blocked, but it was passed through",
+			stringify!($req)
+		);
+		response
+	}};
+}
+
+/// Asserts that sending the given request through the given service (with a [`Passthrough`] at
+/// the bottom) makes it through to the [`Passthrough`]. Requires the `test-util` feature.
+///
+/// [`Passthrough`]: crate::test_util::Passthrough
+#[macro_export]
+macro_rules! assert_passed {
+	($service:expr, $req:expr $(,)?) => {{
+		let response = $crate::test_util::call(&mut $service, $req)
+			.unwrap_or_else(|_| panic!("the service returned an error"));
+		assert!(
+			$crate::test_util::passed(&response),
+			"expected {} to be passed through, but it was blocked with a {}",
+			stringify!($req),
+			response.status()
+		);
+		response
+	}};
+}
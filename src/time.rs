@@ -0,0 +1,57 @@
+// Everything in this crate that needs to know the current time gets it through here, since std
+// can't tell the time on some targets (most notably `wasm32-unknown-unknown`). With the `wasm`
+// feature, the time comes from the JavaScript host instead.
+
+use std::time::Duration;
+
+/// A source of the current time for a [`NoAiLayer`], which can be replaced with
+/// [`NoAiLayer::clock`] so that tests and simulations don't depend on the real time
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use tower_no_ai::{Clock, NoAiLayer, RefetchQuery};
+///
+/// struct Fixed;
+///
+/// impl Clock for Fixed {
+///     fn since_epoch(&self) -> Duration {
+///         Duration::from_secs(1_700_000_000)
+///     }
+/// }
+///
+/// let layer = NoAiLayer::new("https://example.com")
+///     .refetch_query(RefetchQuery::Timestamp)
+///     .clock(Fixed);
+/// ```
+///
+/// [`NoAiLayer`]: crate::NoAiLayer
+/// [`NoAiLayer::clock`]: crate::NoAiLayer::clock
+pub trait Clock: Send + Sync + 'static {
+	/// Returns how long it's been since the unix epoch
+	fn since_epoch(&self) -> Duration;
+}
+
+/// The [`Clock`] that's used by default, which tells the real time
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+	fn since_epoch(&self) -> Duration {
+		since_epoch()
+	}
+}
+
+/// Returns how long it's been since the unix epoch, or zero if the system clock is set to before
+/// it
+pub(crate) fn since_epoch() -> Duration {
+	#[cfg(not(feature = "wasm"))]
+	use std::time::{SystemTime, UNIX_EPOCH};
+
+	#[cfg(feature = "wasm")]
+	use web_time::{SystemTime, UNIX_EPOCH};
+
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+}